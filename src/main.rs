@@ -1,23 +1,111 @@
+// explicit returns are the house style
+#![allow(clippy::needless_return)]
+
 use colored::*;
-use num_traits::ToPrimitive;
-use console::Term;
+use console::{Key, Term};
+use std::io::Write;
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{Receiver, Sender};
+use std::time::{Duration, Instant};
+// screen size in "██" pixels, when the size of the terminal can't be found
 // unintuitively, Y is horizontal
 const SCREEN_Y_SIZE: usize = 50;
 // and X is vertical
 const SCREEN_X_SIZE: usize = 30;
 
-const WORLD_SIZE: usize = 10;
-
-const SIZE_MULTIPLIER: f32 = 10.0;
-
-const PI: f32 = 3.14159265358979323846264338327950288419716939937510582;
-
-const CAMERA_DISTANCE: f32 = 5.0; // this is kind of like FOV but in theory (UNTESTED) higher value = lower FOV.
-const SUN_DIRECTION: Vector3 = Vector3{x: 0.0, y: 0.0, z: -1.0};
+// where the world is loaded from, and saved to whenever it changes
+const WORLD_FILE: &str = "world.rmc";
+// what binary world files start with, and the newest version of the format (see `save_world`)
+const WORLD_FILE_MAGIC: &[u8] = b"RMCW";
+const WORLD_FILE_VERSION: u8 = 1;
+// the 2 ways the cubes in a binary world file can be written
+const WORLD_FILE_RUNS: u8 = 0;
+const WORLD_FILE_PACKED: u8 = 1;
+// the world is stored (and its triangles cached) in chunks of CHUNK_SIZE³ cubes
+const CHUNK_SIZE: i32 = 16;
+const CHUNK_VOLUME: usize = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+const PI: f32 = std::f32::consts::PI;
+
+// vertical field of view, in radians
+const FIELD_OF_VIEW: f32 = 70.0 * (PI/180.0);
+// anything closer than NEAR_PLANE or further than FAR_PLANE is not drawn
+const NEAR_PLANE: f32 = 0.1;
+const FAR_PLANE: f32 = 100.0;
+// the game (movement and everything else) updates this many times per second
+const TICKS_PER_SECOND: f32 = 20.0;
+// if rendering falls this many ticks behind, the lost time is skipped instead of caught up on
+const MAX_TICKS_PER_FRAME: usize = 10;
+// changes to the world are saved at most this often (and when quitting), not after every single one
+const SAVE_INTERVAL: Duration = Duration::from_secs(5);
+// default frame cap, change with `--fps=`
+const FRAMES_PER_SECOND: f32 = 30.0;
+// how far one key press moves the camera when flying
+const MOVEMENT_STEP: f32 = 1.0;
+// anything that falls below this height has fallen out of the world
+const VOID_HEIGHT: f32 = -20.0;
+// size of the player's bounding box, in cubes
+const PLAYER_WIDTH: f32 = 0.6;
+const PLAYER_HEIGHT: f32 = 1.8;
+// how far above the bottom of the bounding box the camera is
+const EYE_HEIGHT: f32 = 1.6;
+// walking physics, in cubes per second (and per second squared)
+const WALK_SPEED: f32 = 4.3;
+const JUMP_SPEED: f32 = 9.0;
+const GRAVITY: f32 = 32.0;
+const TERMINAL_VELOCITY: f32 = 40.0;
+// how much of the walking speed is left after each tick. terminals only say when a key gets pressed,
+// not when it's let go, so this is what makes the player stop once the key stops repeating
+const FRICTION: f32 = 0.6;
+// how far away the player can reach cubes
+const REACH: f32 = 5.0;
+// how far one key press turns the camera, in radians
+const ROTATION_STEP: f32 = 5.0 * (PI/180.0);
+// looking further up or down than straight up or down would flip the camera upside down
+const MAX_PITCH: f32 = 89.0 * (PI/180.0);
+// direction the sunlight travels in (doesn't need to be normalized)
+const SUN_DIRECTION: Vector3 = Vector3{x: 0.4, y: -1.0, z: 0.7};
+// how bright faces that the sun doesn't reach still are, from 0 to 1
+const AMBIENT_LIGHT: f32 = 0.4;
+// how bright a face's corner is depending on how many of the 3 cubes around it are solid
+// (index 0 = boxed in, 3 = nothing around it)
+const AMBIENT_OCCLUSION_LIGHT: [f32; 4] = [0.45, 0.65, 0.85, 1.0];
 
 const GRASS_COLOR: CustomColor = CustomColor { r: 0, g: 255, b: 0 };
 const STONE_COLOR: CustomColor = CustomColor { r: 128, g: 128, b: 128 };
 const WOOD_COLOR: CustomColor = CustomColor { r: 128, g: 128, b: 0 };
+const DIRT_COLOR: CustomColor = CustomColor { r: 134, g: 96, b: 67 };
+// background, and what things fade into in the distance
+const SKY_COLOR: CustomColor = CustomColor { r: 135, g: 190, b: 235 };
+// what `--fog=linear` and `--fog=exponential` use when not given their numbers
+const LINEAR_FOG: Fog = Fog::Linear { start: 8.0, end: 40.0 };
+const EXPONENTIAL_FOG: Fog = Fog::Exponential { density: 0.06 };
+// default fog, change with `--fog=`
+const FOG: Fog = LINEAR_FOG;
+
+// generated terrain: hills TERRAIN_SCALE cubes across, TERRAIN_HEIGHT cubes high on average,
+// give or take about TERRAIN_AMPLITUDE
+const TERRAIN_SCALE: f32 = 24.0;
+const TERRAIN_HEIGHT: f32 = 10.0;
+const TERRAIN_AMPLITUDE: f32 = 8.0;
+// smaller and smaller bumps on top of the hills
+const TERRAIN_OCTAVES: u32 = 3;
+// how much dirt is between the grass and the stone
+const DIRT_DEPTH: i32 = 3;
+// `--new-world` generates this many chunks along x and z, around 0, 0
+const NEW_WORLD_CHUNKS: i32 = 4;
+
+// the cubes that can be placed, selected with the number keys
+const HOTBAR: [CubeType; 4] = [CubeType::Grass, CubeType::Dirt, CubeType::Stone, CubeType::Wood];
+// size of a hotbar slot in pixels, border included
+const HOTBAR_SLOT_SIZE: usize = 4;
+const HOTBAR_BORDER_COLOR: CustomColor = CustomColor { r: 40, g: 40, b: 40 };
+const HOTBAR_SELECTED_COLOR: CustomColor = CustomColor { r: 255, g: 255, b: 255 };
+const CROSSHAIR_COLOR: CustomColor = CustomColor { r: 255, g: 255, b: 255 };
+const TEXT_COLOR: CustomColor = CustomColor { r: 255, g: 255, b: 255 };
+// panels darken what's behind them, so text on them can be read whatever the world looks like
+const PANEL_COLOR: CustomColor = CustomColor { r: 0, g: 0, b: 0 };
+const PANEL_OPACITY: f32 = 0.6;
 
 fn main() {
   //        y
@@ -26,455 +114,1457 @@ fn main() {
   //     `. |
   //       `+------ x
   //        E
-  let mut camera_position: Vector3 = Vector3 { x: 0.0, y: 11.0, z: 0.0 };
-  // achtung! CAMERA ROTATION IS IN RADIANS     convert to radians
-  let mut camera_rotation_vertical: f32 = 0.0 * (PI/180.0);
-  let mut camera_rotation_horizontal: f32 = 0.0 * (PI/180.0);
+  // `--half-block` fits 2 pixels in each character instead of using 2 characters per pixel
+  let pixel_mode: PixelMode = if std::env::args().any(|arg| arg == "--half-block") {
+    PixelMode::HalfBlock
+  } else {
+    PixelMode::FullBlock
+  };
+
+  // `--colors=truecolor`, `--colors=256` or `--colors=16` for terminals that can't show every color,
+  // otherwise go with whatever the terminal says it can do
+  let color_mode: ColorMode = match std::env::args().find_map(|arg| arg.strip_prefix("--colors=").map(String::from)) {
+    Some(name) => ColorMode::from_name(&name).expect("unknown color mode, use truecolor, 256 or 16"),
+    None => match std::env::var("COLORTERM") {
+      Ok(colorterm) if colorterm == "truecolor" || colorterm == "24bit" => ColorMode::TrueColor,
+      _ => ColorMode::Xterm256,
+    },
+  };
+
+  // `--fps=60` renders up to 60 frames per second, the game itself runs at the same speed either way
+  let frames_per_second: f32 = match std::env::args().find_map(|arg| arg.strip_prefix("--fps=").map(String::from)) {
+    Some(fps) => fps.parse().expect("--fps needs a number"),
+    None => FRAMES_PER_SECOND,
+  };
+  // so few frames per second that a frame would last longer than a Duration can hold fails here too
+  let frame_duration: Duration = match Duration::try_from_secs_f32(1.0 / frames_per_second) {
+    Ok(frame_duration) if frames_per_second.is_finite() && frames_per_second > 0.0 => frame_duration,
+    _ => panic!("--fps needs a number above 0, and not so close to 0 that a frame would last forever"),
+  };
+
+  // `--fog=linear:8:40` (start and end) or `--fog=exponential:0.06` (density), the numbers can be left out
+  let fog: Fog = match std::env::args().find_map(|arg| arg.strip_prefix("--fog=").map(String::from)) {
+    Some(name) => Fog::from_name(&name).expect("unknown fog, use linear, linear:START:END, exponential or exponential:DENSITY"),
+    None => FOG,
+  };
 
-  // initialise the screen
-  let mut main_screen: Screen = Screen { pixels: Vec::new(), size_x: SCREEN_X_SIZE, size_y: SCREEN_Y_SIZE };
+  let mut terminal_output: TerminalOutput = TerminalOutput::new(pixel_mode, color_mode);
+  clearscreen::clear().expect("failed to clear screen");
 
   // initialise the world
+  // `--new-world` (or `--new-world=1234` to pick the seed) replaces the world with freshly generated terrain
+  let new_world_seed: Option<u32> = std::env::args().find_map(|arg| {
+    if arg == "--new-world" {
+      // any seed will do
+      let time: Duration = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).expect("time went backwards");
+      return Some(time.subsec_nanos() ^ time.as_secs() as u32);
+    }
+    return arg.strip_prefix("--new-world=").map(|seed| seed.parse().expect("--new-world needs a whole number as the seed"));
+  });
   // put the whole world in memory because reading from disk is slow
-  let mut world_data: Vec<CubeType> = load_world("world.rmc");
+  let mut world: World = match new_world_seed {
+    Some(seed) => {
+      let world: World = generate_world(seed);
+      save_world(WORLD_FILE, &world).expect("failed to save the world");
+      world
+    }
+    None => load_world(WORLD_FILE),
+  };
+  let mut player: Player = Player::new(spawn_position(&world));
+  // the world's triangles only get rebuilt when the world changes, not every frame
+  let mut world_mesh: WorldMesh = WorldMesh::new(&world);
 
-  // GAME LOOP
-  loop {
+  // frame time, position and such in the corner of the screen, toggled with h
+  let mut show_debug: bool = true;
 
-    let stdout = Term::buffered_stdout();
+  // read keys on their own thread, so the game keeps going while nothing is pressed
+  let keys: Receiver<Key> = spawn_input_thread();
 
-    let char = stdout.read_char().expect("ass");
+  let tick_duration: Duration = Duration::from_secs_f32(1.0 / TICKS_PER_SECOND);
+  let mut next_tick: Instant = Instant::now();
+  // the world changed since it was last saved
+  let mut unsaved_changes: bool = false;
+  let mut last_save: Instant = Instant::now();
+  let mut quit: bool = false;
 
-    if char == 'w' {
-      camera_position.z += 1.0;
-    }
-    if char == 'a' {
-      camera_position.x += -1.0;
-    }
-    if char == 's' {
-      camera_position.z += -1.0;
+  // GAME LOOP
+  while !quit {
+    let frame_start_time: Instant = Instant::now();
+
+    // run the ticks that were due since the last frame, so the game runs at the same speed
+    // however fast (or slow) it renders
+    let mut ticks: usize = 0;
+    while next_tick <= frame_start_time {
+      // every key pressed since the last tick
+      for key in keys.try_iter() {
+        player.handle_key(&key);
+        if key == Key::Char('h') {
+          show_debug = !show_debug;
+        }
+        if is_quit_key(&key) {
+          quit = true;
+        }
+        // break the cube being looked at, or put one against it
+        let changed: Option<[i32; 3]> = if key == Key::Char('b') {
+          player.break_target(&mut world)
+        } else if key == Key::Char('n') {
+          player.place_on_target(&mut world)
+        } else {
+          None
+        };
+        if let Some(position) = changed {
+          world_mesh.mark_dirty(position);
+          unsaved_changes = true;
+        }
+      }
+      player.tick(&world);
+      next_tick += tick_duration;
+      ticks += 1;
+      // way behind (the computer was suspended or something), skip ahead instead of catching up
+      if ticks == MAX_TICKS_PER_FRAME {
+        next_tick = frame_start_time + tick_duration;
+      }
     }
-    if char == 'd' {
-      camera_position.x += 1.0;
+    if unsaved_changes && (quit || last_save.elapsed() >= SAVE_INTERVAL) {
+      save_world(WORLD_FILE, &world).expect("failed to save the world");
+      unsaved_changes = false;
+      last_save = Instant::now();
     }
-    if char == 'q' {
-      camera_position.y += -1.0;
+
+    // follow the size of the terminal, in case it was resized
+    let (screen_size_x, screen_size_y): (usize, usize) = match Term::stdout().size_checked() {
+      Some((rows, columns)) => screen_size(rows as usize, columns as usize, pixel_mode),
+      None => screen_size(SCREEN_X_SIZE + 1, SCREEN_Y_SIZE * 2, pixel_mode),
+    };
+
+    // update main screen
+    let render_start_time: Instant = Instant::now();
+    world_mesh.update(&world);
+    let mut main_screen: Screen = draw_world(&world_mesh, player.position, player.rotation_vertical, player.rotation_horizontal, fog, screen_size_x, screen_size_y);
+    let render_duration: Duration = render_start_time.elapsed();
+
+    // the HUD goes on top
+    let mut overlay: Overlay = Overlay::new(main_screen.size_x, main_screen.size_y);
+    overlay.draw_crosshair();
+    draw_hotbar(&mut overlay, player.selected_slot);
+    if show_debug {
+      let mut lines: Vec<String> = vec![
+        format!("RENDER {:.1}MS", render_duration.as_secs_f32() * 1000.0),
+        format!("POS {:.1} {:.1} {:.1}", player.position.x, player.position.y, player.position.z),
+        format!("ROT {:.0}° {:.0}° {:?}", player.rotation_horizontal.to_degrees(), player.rotation_vertical.to_degrees(), player.movement_mode),
+      ];
+      if let Some(hit) = player.target(&world) {
+        lines.push(format!("{:?} {} {} {} {:.1} AWAY", hit.cube_type, hit.position[0], hit.position[1], hit.position[2], hit.distance));
+      }
+      overlay.draw_label(1, 1, &lines);
     }
-    if char == 'e' {
-      camera_position.y += 1.0;
+    overlay.composite(&mut main_screen);
+
+    // Draw the screen, only redrawing what changed since last frame
+    terminal_output.present(&main_screen);
+
+    // wait for the next frame, so it doesn't render faster than the frame cap
+    let frame_time: Duration = frame_start_time.elapsed();
+    if frame_time < frame_duration {
+      std::thread::sleep(frame_duration - frame_time);
     }
-    if char == 'y' {
-      camera_rotation_horizontal += -5.0;
+  }
+  clearscreen::clear().expect("failed to clear screen");
+}
+
+/// Esc and Ctrl-C both save and quit
+fn is_quit_key(key: &Key) -> bool {
+  return *key == Key::Escape || *key == Key::CtrlC;
+}
+
+/// read key presses on a separate thread, so waiting for them doesn't stop the game.
+///
+/// the keys come out of the receiver in the order they were pressed
+fn spawn_input_thread() -> Receiver<Key> {
+  let (sender, receiver): (Sender<Key>, Receiver<Key>) = std::sync::mpsc::channel();
+  std::thread::spawn(move || {
+    let stdout: Term = Term::stdout();
+    // without a terminal there's nothing to read, read_key would just return Key::Unknown forever
+    if !stdout.is_term() {
+      return;
     }
-    if char == 'x' {
-      camera_rotation_horizontal += 5.0;
+    // raw, so Ctrl-C comes through as a key (and the world gets saved) instead of killing the game
+    while let Ok(key) = stdout.read_key_raw() {
+      // nobody listening anymore
+      if sender.send(key).is_err() {
+        return;
+      }
     }
+  });
+  return receiver;
+}
 
-    //println!("char: {:?}", char);
+/// everything about whoever is walking around the world
+struct Player {
+  /// of the camera
+  position: Vector3,
+  /// pitch, in radians
+  rotation_vertical: f32,
+  /// yaw, in radians
+  rotation_horizontal: f32,
+  movement_mode: MovementMode,
+  /// which slot of the HOTBAR is selected, that's what gets placed
+  selected_slot: usize,
+  /// in cubes per second, only used when walking
+  velocity: Vector3,
+  /// standing on something, so able to jump
+  on_ground: bool,
+  /// where the player started, and goes back to after falling out of the world
+  spawn_position: Vector3,
+}
 
-    
-    // update main screen
-    let frame_start_time = std::time::Instant::now();
-    main_screen = draw_world(world_data.clone(), camera_position, camera_rotation_vertical, camera_rotation_horizontal);
-    let frame_duration = frame_start_time.elapsed();
-    // update main screen
-    
-    // Draw the screen and sleep for a few milliseconds (to let the screen render)
-    clearscreen::clear().expect("failed to clear screen");
-    main_screen.draw();
-    let idle_interval = std::time::Duration::from_millis(10);
-    //std::thread::sleep(idle_interval);
-    println!("RENDER : {:?}", frame_duration);
-    println!("POS    : {:?}", camera_position);
-    println!("CAM_ROT: {:?}", camera_rotation_horizontal);
-    
+impl Player {
+  fn new(position: Vector3) -> Player {
+    return Player {
+      position,
+      // achtung! CAMERA ROTATION IS IN RADIANS     convert to radians
+      rotation_vertical: 0.0 * (PI/180.0),
+      rotation_horizontal: 0.0 * (PI/180.0),
+      movement_mode: MovementMode::Fly,
+      selected_slot: 0,
+      velocity: Vector3 { x: 0.0, y: 0.0, z: 0.0 },
+      on_ground: false,
+      spawn_position: position,
+    };
   }
-}
 
-fn draw_world(world_data: Vec<CubeType>, camera_position: Vector3, camera_rotation_vertical: f32, camera_rotation_horizontal: f32) -> Screen {
+  /// the cube the player is looking at, if it's within reach
+  fn target(&self, world: &World) -> Option<RaycastHit> {
+    let forward: Vector3 = angle_couple_to_vector3(self.rotation_horizontal, self.rotation_vertical);
+    return raycast(world, self.position, forward, REACH);
+  }
 
-  let mut triangles_to_draw: Vec<Triangle3D> = Vec::new();
+  /// the type of cube that gets placed
+  fn selected_cube(&self) -> CubeType {
+    return HOTBAR[self.selected_slot];
+  }
 
-  for (linear_index, cube_type) in world_data.iter().enumerate() {
-    // now we must, for each index:
-    // DONE - if air, ignore
-    // DONE - calculate position from index
-    // DONE - find all cube edge vertices
-    // DONE UNTESTED - construct all 12 triangles into Triangle3Ds and give them preassigned normals
-    // DONE UNTESTED - if (dot product is negative), ignore the fucker
-    // DONE - render whatever remains into list of Triangle2D with depth attached
-    //   DONE - if depth is negative dont render
-    // - draw to screen in order (since deepest get drawn first, less depp ones will get drawn on top of deep ones :thumbsup:)
+  /// turn the cube the player is looking at into air, returns where it was
+  fn break_target(&self, world: &mut World) -> Option<[i32; 3]> {
+    let hit: RaycastHit = self.target(world)?;
+    world.set(hit.position, CubeType::Air);
+    return Some(hit.position);
+  }
 
-    if cube_type == &CubeType::Air {
-      continue; // ignore air blocks
+  /// put the selected cube against the face the player is looking at, returns where it went.
+  ///
+  /// nothing happens if that's where the player is standing
+  fn place_on_target(&self, world: &mut World) -> Option<[i32; 3]> {
+    let hit: RaycastHit = self.target(world)?;
+    // the camera is inside the cube, there's no face to put anything against
+    if hit.normal == [0; 3] {
+      return None;
+    }
+    let position: [i32; 3] = [0, 1, 2].map(|axis| hit.position[axis] + hit.normal[axis]);
+    if self.bounding_box().overlaps_cell(position) {
+      return None;
     }
+    world.set(position, self.selected_cube());
+    return Some(position);
+  }
 
-    let cube_color: CustomColor = match cube_type {
-      CubeType::Grass => GRASS_COLOR,
-      CubeType::Stone => STONE_COLOR,
-      CubeType::Wood  => WOOD_COLOR,
-      _ => panic!()
+  /// the space the player takes up, the camera sits EYE_HEIGHT above the bottom of it
+  fn bounding_box(&self) -> BoundingBox {
+    let feet: f32 = self.position.y - EYE_HEIGHT;
+    return BoundingBox {
+      min: [self.position.x - PLAYER_WIDTH / 2.0, feet, self.position.z - PLAYER_WIDTH / 2.0],
+      max: [self.position.x + PLAYER_WIDTH / 2.0, feet + PLAYER_HEIGHT, self.position.z + PLAYER_WIDTH / 2.0],
     };
+  }
 
-    // Let's call our cube ABCDEFGH
-    //    A      B
-    //    +------+.    
-    //    |`. D  | `.  
-    //    |  `+--+---+ C
-    //    |   |  |   | 
-    //    +---+--+.  | 
-    //    H`. |  G `.| 
-    //       `+------+ 
-    //       E       F
-    // Coordinates for reference
-    //        y
-    //        |
-    //    z   |
-    //     `. |
-    //       `+------ x
-    //        E
-    //
-    // normals
-    // +-----------+
-    // |\          |\
-    // | \     T   | \
-    // |  \   Ba   |  \
-    // |   +-----------+
-    // | L |       | R |
-    // +---|-------+   |
-    //  \  |     F  \  |
-    //   \ |   B     \ |
-    //    \|          \|
-    //     +-----------+
-    //
-    //    face       xyz
-    // B  (bottom) = ( -1  0  0 )
-    // Ba (back)   = (  0  1  0 )
-    // L  (left)   = (  0  0 -1 )
-    // R  (right)  = (  0  0  1 )
-    // F  (front)  = (  0 -1  0 )
-    // T  (top)    = (  1  0  0 )
-
-    // construct all vertices of a cube.
-    let vertex_e: Vector3 = linear_index_to_vector3(linear_index);
-    let vertex_f: Vector3 = Vector3{ z: vertex_e.x      , y: vertex_e.y      , x: vertex_e.z + 1.0};
-    let vertex_h: Vector3 = Vector3{ z: vertex_e.x      , y: vertex_e.y + 1.0, x: vertex_e.z      };
-    let vertex_g: Vector3 = Vector3{ z: vertex_e.x      , y: vertex_e.y + 1.0, x: vertex_e.z + 1.0};
-    let vertex_d: Vector3 = Vector3{ z: vertex_e.x + 1.0, y: vertex_e.y      , x: vertex_e.z      };
-    let vertex_c: Vector3 = Vector3{ z: vertex_e.x + 1.0, y: vertex_e.y      , x: vertex_e.z + 1.0};
-    let vertex_b: Vector3 = Vector3{ z: vertex_e.x + 1.0, y: vertex_e.y + 1.0, x: vertex_e.z + 1.0};
-    let vertex_a: Vector3 = Vector3{ z: vertex_e.x + 1.0, y: vertex_e.y + 1.0, x: vertex_e.z      };
-
-
-    let mut triangles: Vec<Triangle3D> = Vec::new();
-
-    // FRONT
-    // Construct ECF
-    triangles.push( Triangle3D {
-      a: vertex_e,
-      b: vertex_c,
-      c: vertex_f,
-      color: cube_color, 
-      normal: Vector3 { y: 0.0, z: -1.0, x: 0.0 },
-    });
-    // Construct ECD
-    triangles.push( Triangle3D {
-      a: vertex_e,
-      b: vertex_c,
-      c: vertex_d,
-      color: cube_color, 
-      normal: Vector3 { y: 0.0, z: -1.0, x: 0.0 },
-    });
-    // BACK
-    // construct ABG
-    triangles.push( Triangle3D {
-      a: vertex_a,
-      b: vertex_b,
-      c: vertex_g,
-      color: cube_color, 
-      normal: Vector3 { y: 0.0, z: 1.0, x: 0.0 },
-    });
-    // construct AHG
-    triangles.push( Triangle3D {
-      a: vertex_a,
-      b: vertex_h,
-      c: vertex_g,
-      color: cube_color, 
-      normal: Vector3 { y: 0.0, z: 1.0, x: 0.0 },
-    });
-    // TOP
-    // construct ADC
-    triangles.push( Triangle3D {
-      a: vertex_a,
-      b: vertex_d,
-      c: vertex_c,
-      color: cube_color, 
-      normal: Vector3 { y: 1.0, z: 0.0, x: 0.0 },
-    });
-    // construct BAC
-    triangles.push( Triangle3D {
-      a: vertex_b,
-      b: vertex_a,
-      c: vertex_c,
-      color: cube_color, 
-      normal: Vector3 { y: 1.0, z: 0.0, x: 0.0 },
-    });
-    // BOTTOM
-    // construct HEG
-    triangles.push( Triangle3D {
-      a: vertex_h,
-      b: vertex_e,
-      c: vertex_g,
-      color: cube_color, 
-      normal: Vector3 { y: -1.0, z: 0.0, x: 0.0 },
-    });
-    // construct GFE
-    triangles.push( Triangle3D {
-      a: vertex_g,
-      b: vertex_f,
-      c: vertex_e,
-      color: cube_color, 
-      normal: Vector3 { y: -1.0, z: 0.0, x: 0.0 },
-    });
-    // LEFT
-    // construct ADH
-    triangles.push( Triangle3D {
-      a: vertex_a,
-      b: vertex_d,
-      c: vertex_h,
-      color: cube_color, 
-      normal: Vector3 { y: 0.0, z: 0.0, x: -1.0 },
-    });
-    // construct DHE
-    triangles.push( Triangle3D {
-      a: vertex_d,
-      b: vertex_h,
-      c: vertex_e,
-      color: cube_color, 
-      normal: Vector3 { y: 0.0, z: 0.0, x: -1.0 },
-    });
-    // RIGHT
-    // construct FGB
-    triangles.push( Triangle3D {
-      a: vertex_f,
-      b: vertex_g,
-      c: vertex_b,
-      color: cube_color, 
-      normal: Vector3 { y: 0.0, z: 0.0, x: 1.0 },
-    });
-    // construct BFC
-    triangles.push( Triangle3D {
-      a: vertex_b,
-      b: vertex_f,
-      c: vertex_c,
-      color: cube_color, 
-      normal: Vector3 { y: 0.0, z: 0.0, x: 1.0 },
-    });
+  /// move as far as possible along `motion` without going into any cubes, one axis at a time so
+  /// the player slides along walls instead of sticking to them.
+  ///
+  /// returns which axes got blocked
+  fn move_and_collide(&mut self, world: &World, motion: Vector3) -> [bool; 3] {
+    let motion: [f32; 3] = [motion.x, motion.y, motion.z];
+    let mut blocked: [bool; 3] = [false; 3];
+    // vertical first, so landing on something happens before sliding over it
+    for axis in [1, 0, 2] {
+      let distance: f32 = self.bounding_box().sweep(world, axis, motion[axis]);
+      blocked[axis] = distance != motion[axis];
+      let mut position: [f32; 3] = [self.position.x, self.position.y, self.position.z];
+      position[axis] += distance;
+      self.position = array_to_vector3(position);
+    }
+    return blocked;
+  }
 
-    let camera_rotation_vectorial: Vector3 = angle_couple_to_vector3(camera_rotation_horizontal, camera_rotation_vertical);
+  /// advance the physics by one tick
+  fn tick(&mut self, world: &World) {
+    // flying goes straight through everything
+    if self.movement_mode != MovementMode::Walk {
+      return;
+    }
+    let delta_time: f32 = 1.0 / TICKS_PER_SECOND;
+    self.velocity.y = (self.velocity.y - GRAVITY * delta_time).max(-TERMINAL_VELOCITY);
+
+    let motion: Vector3 = Vector3 { x: self.velocity.x * delta_time, y: self.velocity.y * delta_time, z: self.velocity.z * delta_time };
+    let blocked: [bool; 3] = self.move_and_collide(world, motion);
+    self.on_ground = blocked[1] && self.velocity.y < 0.0;
+    if blocked[0] {
+      self.velocity.x = 0.0;
+    }
+    if blocked[1] {
+      self.velocity.y = 0.0;
+    }
+    if blocked[2] {
+      self.velocity.z = 0.0;
+    }
+    self.velocity.x *= FRICTION;
+    self.velocity.z *= FRICTION;
 
-    for triangle in triangles {
-      // if the normal is not negative, add to triangles to draw list
-      if vector3_dot(camera_rotation_vectorial, triangle.normal) < 0.0 {
-        triangles_to_draw.push(triangle);
+    if self.position.y < VOID_HEIGHT {
+      self.position = self.spawn_position;
+      self.velocity = Vector3 { x: 0.0, y: 0.0, z: 0.0 };
+    }
+  }
+
+  /// act on a single key press
+  fn handle_key(&mut self, key: &Key) {
+    // move relative to where the camera is looking
+    let mut forward_amount: f32 = 0.0;
+    let mut right_amount: f32 = 0.0;
+    if *key == Key::Char('w') {
+      forward_amount += 1.0;
+    }
+    if *key == Key::Char('a') {
+      right_amount += -1.0;
+    }
+    if *key == Key::Char('s') {
+      forward_amount += -1.0;
+    }
+    if *key == Key::Char('d') {
+      right_amount += 1.0;
+    }
+    // pick a hotbar slot, '1' is the first one
+    if let Key::Char(digit @ '1'..='9') = key {
+      let slot: usize = *digit as usize - '1' as usize;
+      if slot < HOTBAR.len() {
+        self.selected_slot = slot;
+      }
+    }
+    if *key == Key::Char('g') {
+      self.velocity = Vector3 { x: 0.0, y: 0.0, z: 0.0 };
+      self.movement_mode = match self.movement_mode {
+        MovementMode::Fly => MovementMode::Walk,
+        MovementMode::Walk => MovementMode::Fly,
+      };
+    }
+    let direction: Vector3 = movement_vector(self.rotation_horizontal, forward_amount, right_amount);
+    match self.movement_mode {
+      MovementMode::Fly => {
+        self.position = vector3_add(self.position, Vector3 { x: direction.x * MOVEMENT_STEP, y: 0.0, z: direction.z * MOVEMENT_STEP });
+        if *key == Key::Char('q') {
+          self.position.y += -MOVEMENT_STEP;
+        }
+        if *key == Key::Char('e') {
+          self.position.y += MOVEMENT_STEP;
+        }
       }
+      MovementMode::Walk => {
+        // start walking that way, the actual moving happens in tick()
+        if forward_amount != 0.0 || right_amount != 0.0 {
+          self.velocity.x = direction.x * WALK_SPEED;
+          self.velocity.z = direction.z * WALK_SPEED;
+        }
+        if *key == Key::Char(' ') && self.on_ground {
+          self.velocity.y = JUMP_SPEED;
+          self.on_ground = false;
+        }
+      }
+    }
+    // look left and right
+    if *key == Key::ArrowLeft || *key == Key::Char('y') {
+      self.rotation_horizontal -= ROTATION_STEP;
+    }
+    if *key == Key::ArrowRight || *key == Key::Char('x') {
+      self.rotation_horizontal += ROTATION_STEP;
     }
+    // keep the angle small, so it doesn't lose precision after spinning around a lot
+    self.rotation_horizontal = self.rotation_horizontal.rem_euclid(2.0 * PI);
+    // look up and down
+    if *key == Key::ArrowUp || *key == Key::Char('r') {
+      self.rotation_vertical += ROTATION_STEP;
+    }
+    if *key == Key::ArrowDown || *key == Key::Char('f') {
+      self.rotation_vertical -= ROTATION_STEP;
+    }
+    self.rotation_vertical = self.rotation_vertical.clamp(-MAX_PITCH, MAX_PITCH);
   }
+}
 
-  // NOTE: surprisingly this shitty sorting algorithm is only 10-20ms slower than unsorted.
-  // where are the 380ms frame times coming from????????????
+/// an axis aligned box, as `[x, y, z]` so the axis to work on can be picked at runtime
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct BoundingBox {
+  min: [f32; 3],
+  max: [f32; 3],
+}
 
-  // now: render triangle and add it to a sorted list, going from deep to not deep
-  // (the depth of a triangle is how far it is from the camera on the camera's depth axis)
-  // (doing things this way may be problematic in a normal renderer, but in a cube came we're chilling)
+impl BoundingBox {
+  /// true if the box and the cube at `cell` overlap, just touching doesn't count
+  fn overlaps_cell(&self, cell: [i32; 3]) -> bool {
+    return (0..3).all(|axis| self.min[axis] < (cell[axis] + 1) as f32 && self.max[axis] > cell[axis] as f32);
+  }
 
-  // a loop where:
-  // - render a triangle, get its depth as well (this one will later be referred to as "current")
-  // - put it in a struct that contains it and its depth
-  // - create bool already_added = false;
-  // - iterate through already rendered triangles
-  //   - if depth of current is higher than the triangle already rendered and already_added is false
-  //     - add current to new vector
-  //     - set already_added to true
-  //   - else
-  //      - add already rendered to new vector
-  // - set already rendered to new vector
-  // - repeat for the next triangle
-  let mut rendered_triangles: Vec<RenderedTriangle2D> = Vec::new();
-  for triangle in triangles_to_draw {
-      let (rendered_triangle, depth): (Triangle2D, f32) = render_triangle(triangle, camera_position, camera_rotation_vertical, camera_rotation_horizontal);
-      let rendered_triangle_to_append = RenderedTriangle2D {
-          triangle: rendered_triangle,
-          depth,
-      };
-  
-      let mut inserted = false;
-      let mut new_rendered_triangles: Vec<RenderedTriangle2D> = Vec::new();
-      for already_rendered_triangle in rendered_triangles.iter() {
-          if !inserted && already_rendered_triangle.depth > depth {
-              new_rendered_triangles.push(rendered_triangle_to_append.clone());
-              inserted = true;
+  /// how far the box can move `distance` along `axis` before it runs into a cube, or `distance`
+  /// if nothing is in the way.
+  ///
+  /// every cell on the way is checked, so moving fast can't skip through thin walls
+  fn sweep(&self, world: &World, axis: usize, distance: f32) -> f32 {
+    // a little leeway, so touching a cube isn't the same as being inside it
+    const EPSILON: f32 = 0.0001;
+    let axis_u: usize = (axis + 1) % 3;
+    let axis_v: usize = (axis + 2) % 3;
+    // the cells the box covers on the other 2 axes
+    let cells_u = (self.min[axis_u] + EPSILON).floor() as i32..(self.max[axis_u] - EPSILON).ceil() as i32;
+    let cells_v = (self.min[axis_v] + EPSILON).floor() as i32..(self.max[axis_v] - EPSILON).ceil() as i32;
+    let layer_is_solid = |layer: i32| -> bool {
+      for u in cells_u.clone() {
+        for v in cells_v.clone() {
+          let mut cell: [i32; 3] = [0; 3];
+          cell[axis] = layer;
+          cell[axis_u] = u;
+          cell[axis_v] = v;
+          if world.get(cell) != CubeType::Air {
+            return true;
           }
-          new_rendered_triangles.push(already_rendered_triangle.clone());
+        }
+      }
+      return false;
+    };
+
+    if distance > 0.0 {
+      // go through the layers of cells in front of the box until one is solid or out of reach
+      let front: f32 = self.max[axis];
+      let mut layer: i32 = (front - EPSILON).ceil() as i32;
+      while (layer as f32) < front + distance {
+        if layer_is_solid(layer) {
+          return (layer as f32 - front).max(0.0);
+        }
+        layer += 1;
       }
-      if !inserted {
-          new_rendered_triangles.push(rendered_triangle_to_append);
+    } else if distance < 0.0 {
+      let front: f32 = self.min[axis];
+      let mut layer: i32 = (front + EPSILON).floor() as i32 - 1;
+      while (layer + 1) as f32 > front + distance {
+        if layer_is_solid(layer) {
+          return ((layer + 1) as f32 - front).min(0.0);
+        }
+        layer -= 1;
       }
-      rendered_triangles = new_rendered_triangles;
+    }
+    return distance;
+  }
+}
+
+/// flying moves freely in every direction (and through everything), walking is affected by gravity and collisions
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MovementMode {
+  Fly,
+  Walk,
+}
+
+/// how far to move along the world axes, for moving `forward_amount` towards where the camera
+/// looks and `right_amount` to its right.
+///
+/// only the yaw matters, so looking up or down doesn't make the camera move up or down
+fn movement_vector(camera_rotation_horizontal: f32, forward_amount: f32, right_amount: f32) -> Vector3 {
+  let (sin_h, cos_h) = f32::sin_cos(camera_rotation_horizontal);
+  return Vector3 {
+    x: sin_h * forward_amount + cos_h * right_amount,
+    y: 0.0,
+    z: cos_h * forward_amount - sin_h * right_amount,
+  };
+}
+
+fn draw_world(world_mesh: &WorldMesh, camera_position: Vector3, camera_rotation_vertical: f32, camera_rotation_horizontal: f32, fog: Fog, screen_size_x: usize, screen_size_y: usize) -> Screen {
+
+  // now we must:
+  // DONE - build the triangles of every visible cube face (cached in world_mesh)
+  // DONE - if the triangle faces away from the camera (or is behind it), ignore the fucker
+  // DONE - render whatever remains into list of Triangle2D with depth attached
+  //   DONE - clip whatever goes behind the camera, skip whatever is out of view
+  // DONE - draw to screen, the depth buffer takes care of what's in front of what
+
+  let mut triangles_to_draw: Vec<Triangle3D> = Vec::new();
+
+  let camera_forward: Vector3 = angle_couple_to_vector3(camera_rotation_horizontal, camera_rotation_vertical);
+
+  for triangle in world_mesh.triangles() {
+    if is_behind_camera(triangle, camera_position, camera_forward) {
+      continue;
+    }
+    if !faces_camera(triangle, camera_position) {
+      continue;
+    }
+    triangles_to_draw.push(*triangle);
   }
 
-  //println!("{:?}", rendered_triangles.clone());
-  
-  let mut screen: Screen = Screen { pixels: Vec::new(), size_x: SCREEN_X_SIZE, size_y: SCREEN_Y_SIZE };
-  screen.init();
+  // world space -> clip space, computed once for the whole frame
+  // pixels are about as wide as they are tall, so the aspect ratio is just columns / rows
+  let aspect_ratio: f32 = screen_size_y as f32 / screen_size_x as f32;
+  let view_projection: Matrix4 = camera_matrix(camera_position, camera_rotation_vertical, camera_rotation_horizontal, aspect_ratio);
 
-  let mut new_pixels: Vec<Vec<CustomColor>> = Vec::new();
+  let mut screen: Screen = Screen { pixels: Vec::new(), depth: Vec::new(), size_x: screen_size_x, size_y: screen_size_y };
+  screen.init();
 
-  for (x, x_row) in screen.pixels.iter().enumerate().clone() {
-    let mut new_pixels_x: Vec<CustomColor> = Vec::new();
-    for (y, _) in x_row.iter().enumerate() {
-      let mut new_pixel: CustomColor = CustomColor { r: 0, g: 0, b: 0 };
-      for triangle in rendered_triangles.clone() {
-        let x_centered = (x as i32).wrapping_sub(SCREEN_X_SIZE as i32 / 1) as f32;
-        let y_centered = (y as i32).wrapping_sub(SCREEN_Y_SIZE as i32 / 1) as f32;
-        if triangle.triangle.contains(Vector2 { x: x_centered, y: y_centered }) {
-          new_pixel = triangle.triangle.color;
-        }
+  // no sorting needed: the depth buffer keeps whichever triangle is closest, pixel by pixel,
+  // so triangles can be drawn in any order
+  for triangle in triangles_to_draw {
+    for rendered_triangle in render_triangle(triangle, view_projection, screen.size_x, screen.size_y) {
+      for fragment in rendered_triangle.triangle.rasterize(screen.size_x, screen.size_y) {
+        let depth: f32 = rendered_triangle.depth_at(fragment.weights);
+        let color: CustomColor = scale_color(rendered_triangle.triangle.color, rendered_triangle.ambient_occlusion_at(fragment.weights));
+        let color: CustomColor = mix_colors(color, SKY_COLOR, fog.amount(depth));
+        screen.plot(fragment.x, fragment.y, color, depth);
       }
-      new_pixels_x.push(new_pixel);
     }
-    new_pixels.push(new_pixels_x);
   }
-  screen.pixels = new_pixels;
-    
 
   // so the linter shuts up
   return screen;
 }
 
+/// backface culling: the camera has to be on the side of the triangle its normal points to.
+///
+/// with a perspective camera this depends on where the camera is, not where it's looking,
+/// faces at the edge of the view can point sideways and still be seen
+fn faces_camera(triangle: &Triangle3D, camera_position: Vector3) -> bool {
+  return vector3_dot(triangle.normal, vector3_subtract(triangle.a, camera_position)) < 0.0;
+}
+
+/// true if every corner of the triangle is behind the camera, so it can't be seen at all
+fn is_behind_camera(triangle: &Triangle3D, camera_position: Vector3, camera_forward: Vector3) -> bool {
+  return [triangle.a, triangle.b, triangle.c].iter().all(|corner| {
+    return vector3_dot(vector3_subtract(*corner, camera_position), camera_forward) < NEAR_PLANE;
+  });
+}
+
+/// a visible unit face, for the greedy mesher
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct MaskFace {
+  cube_type: CubeType,
+  /// of each corner, see `vertex_ambient_occlusion`
+  ambient_occlusion: [u8; 4],
+}
+
+/// classic voxel ambient occlusion for one corner of a face: how many of the 3 cubes touching
+/// that corner (in front of the face) are solid, as an index into AMBIENT_OCCLUSION_LIGHT.
+///
+/// if both sides are solid the corner is boxed in, whatever the cube in the corner is
+fn vertex_ambient_occlusion(side_1: bool, side_2: bool, corner: bool) -> u8 {
+  if side_1 && side_2 {
+    return 0;
+  }
+  return 3 - (side_1 as u8 + side_2 as u8 + corner as u8);
+}
+
+/// darken a color depending on how directly the face it's on is lit by the sun
+fn shade(color: CustomColor, normal: Vector3) -> CustomColor {
+  let towards_sun: Vector3 = vector3_normalize(Vector3 { x: -SUN_DIRECTION.x, y: -SUN_DIRECTION.y, z: -SUN_DIRECTION.z });
+  // 1 when facing the sun, 0 when at a right angle or facing away from it
+  let diffuse: f32 = f32::max(vector3_dot(vector3_normalize(normal), towards_sun), 0.0);
+  let light: f32 = AMBIENT_LIGHT + (1.0 - AMBIENT_LIGHT) * diffuse;
+  return scale_color(color, light);
+}
+
+/// multiply every channel of a color by `factor`
+fn scale_color(color: CustomColor, factor: f32) -> CustomColor {
+  let scale = |channel: u8| -> u8 {
+    return (channel as f32 * factor).round().clamp(0.0, 255.0) as u8;
+  };
+  return CustomColor { r: scale(color.r), g: scale(color.g), b: scale(color.b) };
+}
+
+/// `from` when `t = 0`, `to` when `t = 1`, and in between for anything in between
+fn mix_colors(from: CustomColor, to: CustomColor, t: f32) -> CustomColor {
+  let mix = |from: u8, to: u8| -> u8 {
+    return (from as f32 + (to as f32 - from as f32) * t).round().clamp(0.0, 255.0) as u8;
+  };
+  return CustomColor { r: mix(from.r, to.r), g: mix(from.g, to.g), b: mix(from.b, to.b) };
+}
+
+/// how things fade into the sky with distance
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Fog {
+  /// no fog until `start`, then thicker and thicker until nothing can be seen past `end`
+  Linear { start: f32, end: f32 },
+  /// thickens quickly up close and slowly further away, `density` is how fast
+  Exponential { density: f32 },
+}
+impl Fog {
+  /// the value of `--fog=...` -> Fog: the kind, then optionally its numbers, separated by `:`
+  fn from_name(name: &str) -> Option<Fog> {
+    let mut parts = name.split(':');
+    let kind: &str = parts.next()?;
+    let numbers: Vec<f32> = parts.map(|number| number.parse().ok()).collect::<Option<Vec<f32>>>()?;
+    match (kind, numbers.as_slice()) {
+      ("linear", [])                               => return Some(LINEAR_FOG),
+      ("linear", [start, end]) if start < end      => return Some(Fog::Linear { start: *start, end: *end }),
+      ("exponential", [])                          => return Some(EXPONENTIAL_FOG),
+      ("exponential", [density]) if *density > 0.0 => return Some(Fog::Exponential { density: *density }),
+      _                                            => return None,
+    }
+  }
+  /// how much fog there is at some depth, from 0 (none) to 1 (only fog)
+  fn amount(&self, depth: f32) -> f32 {
+    let amount: f32 = match self {
+      Fog::Linear { start, end } => (depth - start) / (end - start),
+      Fog::Exponential { density } => 1.0 - f32::exp(-density * depth),
+    };
+    return amount.clamp(0.0, 1.0);
+  }
+}
+
+/// the triangles of the whole world, kept around between frames.
+///
+/// each chunk of the world has its own triangles, so changing a cube only rebuilds the chunks
+/// around it instead of the whole world.
+struct WorldMesh {
+  /// by chunk coordinates, like `World.chunks`. chunks without any visible faces are left out
+  chunks: HashMap<[i32; 3], Vec<Triangle3D>>,
+  /// chunks that need to be rebuilt on the next `update`
+  dirty: HashSet<[i32; 3]>,
+}
+impl WorldMesh {
+  fn new(world: &World) -> WorldMesh {
+    let mut world_mesh: WorldMesh = WorldMesh { chunks: HashMap::new(), dirty: world.chunks.keys().copied().collect() };
+    world_mesh.update(world);
+    return world_mesh;
+  }
+  /// call this after changing the cube at `position`.
+  ///
+  /// faces of the neighbouring cubes may appear or disappear too, and the ambient occlusion of
+  /// faces next to it (diagonals included) changes, so if the cube is on the edge or corner of its
+  /// chunk, every chunk touching it is marked as well.
+  fn mark_dirty(&mut self, position: [i32; 3]) {
+    for offset_x in -1..=1 {
+      for offset_y in -1..=1 {
+        for offset_z in -1..=1 {
+          let neighbour: [i32; 3] = [position[0] + offset_x, position[1] + offset_y, position[2] + offset_z];
+          let (chunk, _): ([i32; 3], usize) = World::chunk_coordinates(neighbour);
+          self.dirty.insert(chunk);
+        }
+      }
+    }
+  }
+  /// rebuild the chunks marked as dirty, returns how many were rebuilt
+  fn update(&mut self, world: &World) -> usize {
+    let rebuilt: usize = self.dirty.len();
+    for chunk in self.dirty.drain() {
+      let chunk_min: [i32; 3] = chunk.map(|coordinate| coordinate * CHUNK_SIZE);
+      let chunk_max: [i32; 3] = chunk_min.map(|min| min + CHUNK_SIZE);
+      let triangles: Vec<Triangle3D> = build_region_mesh(world, chunk_min, chunk_max);
+      if triangles.is_empty() {
+        self.chunks.remove(&chunk);
+      } else {
+        self.chunks.insert(chunk, triangles);
+      }
+    }
+    return rebuilt;
+  }
+  /// every triangle of the world
+  fn triangles(&self) -> impl Iterator<Item = &Triangle3D> {
+    return self.chunks.values().flatten();
+  }
+}
+
+/// build the triangles of every cube face inside a box of the world (`region_min` included,
+/// `region_max` excluded) that could be seen from somewhere.
+///
+/// a face is only kept if the cell it faces is air (which is anywhere nothing was put),
+/// faces squished between two solid cubes are never visible.
+/// neighbouring faces of the same cube type that face the same way are merged into bigger
+/// rectangles (greedy meshing), so a flat floor is 2 triangles instead of 2 per cube.
+fn build_region_mesh(world: &World, region_min: [i32; 3], region_max: [i32; 3]) -> Vec<Triangle3D> {
+  let mut triangles: Vec<Triangle3D> = Vec::new();
+
+  // Coordinates for reference
+  //        y
+  //        |
+  //    z   |
+  //     `. |
+  //       `+------ x
+  //
+  //    face       xyz
+  // B  (bottom) = (  0 -1  0 )
+  // Ba (back)   = (  0  0  1 )
+  // L  (left)   = ( -1  0  0 )
+  // R  (right)  = (  1  0  0 )
+  // F  (front)  = (  0  0 -1 )
+  // T  (top)    = (  0  1  0 )
+
+  // for each of the 6 face directions:
+  // - go through the region one slice at a time, along the axis the faces point to
+  // - in each slice, mark which cells have a visible face in that direction, and of what type
+  // - cover the marked cells with as few rectangles as possible:
+  //   - take the first marked cell, grow it along u as long as the type stays the same
+  //   - then grow that row along v as long as the whole row matches
+  //   - unmark the rectangle, emit it as 2 triangles, repeat
+  for axis in 0..3 {
+    // the 2 axes of the slice
+    let axis_u: usize = (axis + 1) % 3;
+    let axis_v: usize = (axis + 2) % 3;
+    let size_u: usize = (region_max[axis_u] - region_min[axis_u]) as usize;
+    let size_v: usize = (region_max[axis_v] - region_min[axis_v]) as usize;
+
+    for direction in [-1, 1] {
+      let mut normal: [f32; 3] = [0.0; 3];
+      normal[axis] = direction as f32;
+
+      for slice in region_min[axis]..region_max[axis] {
+        // which type of face (if any) is visible at each (u, v) of the slice, relative to the region,
+        // along with the ambient occlusion of its 4 corners
+        let mut mask: Vec<Vec<Option<MaskFace>>> = vec![vec![None; size_v]; size_u];
+        for (u, mask_row) in mask.iter_mut().enumerate() {
+          for (v, cell) in mask_row.iter_mut().enumerate() {
+            let mut position: [i32; 3] = [0; 3];
+            position[axis] = slice;
+            position[axis_u] = region_min[axis_u] + u as i32;
+            position[axis_v] = region_min[axis_v] + v as i32;
+            let cube_type: CubeType = world.get(position);
+            if cube_type == CubeType::Air {
+              continue; // ignore air blocks
+            }
+            // the normal points right at the neighbouring cube
+            position[axis] += direction;
+            if world.get(position) != CubeType::Air {
+              continue; // hidden face
+            }
+            // the cubes around that neighbour darken the corners of the face
+            let solid = |offset_u: i32, offset_v: i32| -> bool {
+              let mut around: [i32; 3] = position;
+              around[axis_u] += offset_u;
+              around[axis_v] += offset_v;
+              return world.get(around) != CubeType::Air;
+            };
+            let mut ambient_occlusion: [u8; 4] = [0; 4];
+            // same order as the corners below
+            for (i, (offset_u, offset_v)) in [(-1, -1), (1, -1), (1, 1), (-1, 1)].iter().enumerate() {
+              ambient_occlusion[i] = vertex_ambient_occlusion(solid(*offset_u, 0), solid(0, *offset_v), solid(*offset_u, *offset_v));
+            }
+            *cell = Some(MaskFace { cube_type, ambient_occlusion });
+          }
+        }
+
+        for u in 0..size_u {
+          for v in 0..size_v {
+            let MaskFace { cube_type, ambient_occlusion } = match mask[u][v] {
+              Some(face) => face,
+              None => continue,
+            };
+            // the occlusion gets interpolated between the corners of the rectangle, so only faces
+            // with the same occlusion on all corners can be merged without changing how they look
+            let mergeable: bool = ambient_occlusion.iter().all(|corner| *corner == ambient_occlusion[0]);
+            let face: Option<MaskFace> = if mergeable { mask[u][v] } else { None };
+
+            // grow along u
+            let mut width: usize = 1;
+            while u + width < size_u && face.is_some() && mask[u + width][v] == face {
+              width += 1;
+            }
+            // grow along v, one full row at a time
+            let mut height: usize = 1;
+            while v + height < size_v && face.is_some() && (u..u + width).all(|row| mask[row][v + height] == face) {
+              height += 1;
+            }
+            // don't use these faces twice
+            for row in mask.iter_mut().skip(u).take(width) {
+              for cell in row.iter_mut().skip(v).take(height) {
+                *cell = None;
+              }
+            }
+
+            // the face sits on the side of the cube the normal points to
+            let plane: f32 = if direction > 0 { slice as f32 + 1.0 } else { slice as f32 };
+            let corner = |corner_u: usize, corner_v: usize| -> Vector3 {
+              let mut corner: [f32; 3] = [0.0; 3];
+              corner[axis] = plane;
+              corner[axis_u] = (region_min[axis_u] + corner_u as i32) as f32;
+              corner[axis_v] = (region_min[axis_v] + corner_v as i32) as f32;
+              return array_to_vector3(corner);
+            };
+            // corners going around the rectangle
+            let corners: [Vector3; 4] = [
+              corner(u,         v         ),
+              corner(u + width, v         ),
+              corner(u + width, v + height),
+              corner(u,         v + height),
+            ];
+
+            let cube_color: CustomColor = cube_type.color();
+            let normal: Vector3 = array_to_vector3(normal);
+            // the sun never moves, so the shading can be done once here instead of every frame
+            let face_color: CustomColor = shade(cube_color, normal);
+            let light: [f32; 4] = ambient_occlusion.map(|occlusion| AMBIENT_OCCLUSION_LIGHT[occlusion as usize]);
+
+            // split the rectangle along the diagonal with the brightest ends, otherwise a single dark corner
+            // bleeds over the whole face and the shading looks different depending on the face's orientation
+            let (first, second, third, fourth): (usize, usize, usize, usize) = if light[0] + light[2] < light[1] + light[3] {
+              (1, 2, 3, 0)
+            } else {
+              (0, 1, 2, 3)
+            };
+            triangles.push(Triangle3D {
+              a: corners[first], b: corners[second], c: corners[third],
+              color: face_color, normal,
+              ambient_occlusion: [light[first], light[second], light[third]],
+            });
+            triangles.push(Triangle3D {
+              a: corners[first], b: corners[third], c: corners[fourth],
+              color: face_color, normal,
+              ambient_occlusion: [light[first], light[third], light[fourth]],
+            });
+          }
+        }
+      }
+    }
+  }
+
+  return triangles;
+}
+
+/// a triangle on screen, with the depth of each of its vertices
 #[derive(Debug, Clone, Copy, PartialEq)]
 struct RenderedTriangle2D {
   triangle: Triangle2D,
-  depth: f32,
+  depth_a: f32,
+  depth_b: f32,
+  depth_c: f32,
+  /// light multiplier at a, b and c, see `Triangle3D`
+  ambient_occlusion: [f32; 3],
+}
+impl RenderedTriangle2D {
+  /// depth at a point of the triangle, given its barycentric coordinates
+  fn depth_at(&self, weights: Vector3) -> f32 {
+    // after the perspective divide, 1/depth is what varies linearly across the screen, not depth itself
+    let inverse_depth: f32 = weights.x / self.depth_a + weights.y / self.depth_b + weights.z / self.depth_c;
+    return 1.0 / inverse_depth;
+  }
+  /// ambient occlusion light multiplier at a point of the triangle, given its barycentric coordinates
+  fn ambient_occlusion_at(&self, weights: Vector3) -> f32 {
+    // same as the depth: interpolate value/depth linearly, then divide by the interpolated 1/depth
+    let [light_a, light_b, light_c] = self.ambient_occlusion;
+    let light_over_depth: f32 = weights.x * light_a / self.depth_a + weights.y * light_b / self.depth_b + weights.z * light_c / self.depth_c;
+    return light_over_depth * self.depth_at(weights);
+  }
 }
 
+/// the direction the camera looks in, as a vector of length 1
+///
+/// horizontal (yaw) turns from +z towards +x, vertical (pitch) turns up towards +y
 fn angle_couple_to_vector3(horizontal: f32, vertical: f32) -> Vector3 {
+  let (sin_h, cos_h) = f32::sin_cos(horizontal);
+  let (sin_v, cos_v) = f32::sin_cos(vertical);
   let vector: Vector3 = Vector3{
-    z: round_hundredth(f32::cos(horizontal)),
-    y: round_hundredth(f32::sin(vertical)),
-    x: round_hundredth(f32::sin(horizontal)),
+    x: sin_h * cos_v,
+    y: sin_v,
+    z: cos_h * cos_v,
   };
   return vector;
 }
-/// round a number to the hundredth <br>
-/// 0.015205001 => 0.01
-fn round_hundredth(num: f32) -> f32 {
-  return (num * 100.0).round() / 100.0;
-}
 
-/// get a set of coordinates with an index
-fn linear_index_to_vector3(linear_index: usize) -> Vector3 {
-  let z = linear_index % WORLD_SIZE;
-  let y = (linear_index / WORLD_SIZE) % WORLD_SIZE;
-  let x = linear_index / (WORLD_SIZE * WORLD_SIZE);
-  return Vector3 { x: x as f32, y: y as f32, z: z as f32};
+/// the first cube a ray runs into
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct RaycastHit {
+  /// coordinates of the cube
+  position: [i32; 3],
+  cube_type: CubeType,
+  /// of the face the ray went in through, so pointing back at where the ray came from.
+  /// all zeros if the ray started inside the cube
+  normal: [i32; 3],
+  /// along the ray, from where it starts to where it enters the cube
+  distance: f32,
 }
-/// get an index from a set of coordinates
-fn vector3_to_linear_index(position: Vector3) -> usize {
-  let z = position.x.to_usize().expect("idc");
-  let y = position.y.to_usize().expect("idc");
-  let x = position.z.to_usize().expect("idc");
-  let linear_index: usize = x + WORLD_SIZE * (y * WORLD_SIZE + z);
-  return linear_index;
+
+/// follow a ray through the world one cell at a time, until it runs into something that isn't air
+/// or gets further than `max_distance` away.
+///
+/// this is the DDA from "A Fast Voxel Traversal Algorithm" (Amanatides & Woo): for each axis, keep
+/// track of how far along the ray the next cell boundary is, and always step over the closest one
+fn raycast(world: &World, origin: Vector3, direction: Vector3, max_distance: f32) -> Option<RaycastHit> {
+  let origin: [f32; 3] = [origin.x, origin.y, origin.z];
+  let direction: Vector3 = vector3_normalize(direction);
+  let direction: [f32; 3] = [direction.x, direction.y, direction.z];
+
+  let mut cell: [i32; 3] = origin.map(|coordinate| coordinate.floor() as i32);
+  // which way the ray goes through the cells on each axis
+  let mut step: [i32; 3] = [0; 3];
+  // how far along the ray the next boundary between cells is, for each axis
+  let mut next_boundary: [f32; 3] = [f32::INFINITY; 3];
+  // and how far the ray goes between 2 boundaries
+  let mut boundary_spacing: [f32; 3] = [f32::INFINITY; 3];
+  for axis in 0..3 {
+    if direction[axis] > 0.0 {
+      step[axis] = 1;
+      next_boundary[axis] = ((cell[axis] + 1) as f32 - origin[axis]) / direction[axis];
+      boundary_spacing[axis] = 1.0 / direction[axis];
+    } else if direction[axis] < 0.0 {
+      step[axis] = -1;
+      next_boundary[axis] = (cell[axis] as f32 - origin[axis]) / direction[axis];
+      boundary_spacing[axis] = -1.0 / direction[axis];
+    }
+  }
+
+  let mut distance: f32 = 0.0;
+  let mut normal: [i32; 3] = [0; 3];
+  while distance <= max_distance {
+    let cube_type: CubeType = world.get(cell);
+    if cube_type != CubeType::Air {
+      return Some(RaycastHit { position: cell, cube_type, normal, distance });
+    }
+    // go into the next cell, through whichever boundary the ray reaches first
+    let axis: usize = if next_boundary[0] < next_boundary[1] {
+      if next_boundary[0] < next_boundary[2] { 0 } else { 2 }
+    } else if next_boundary[1] < next_boundary[2] { 1 } else { 2 };
+    distance = next_boundary[axis];
+    cell[axis] += step[axis];
+    next_boundary[axis] += boundary_spacing[axis];
+    normal = [0; 3];
+    normal[axis] = -step[axis];
+  }
+  return None;
 }
 
-fn load_world(path: &str) -> Vec<CubeType> {
-  let mut cubes: Vec<CubeType> = Vec::new();
+/// every cube of the world, split into chunks of CHUNK_SIZE³ cubes so it can be any size, and grow
+/// in any direction. anything that isn't in a chunk is air
+struct World {
+  /// by chunk coordinates (world coordinates / CHUNK_SIZE, rounded down), laid out like
+  /// `World::chunk_coordinates` says
+  chunks: HashMap<[i32; 3], Vec<CubeType>>,
+}
+impl World {
+  fn new() -> World {
+    return World { chunks: HashMap::new() };
+  }
+  /// which chunk the cube at `position` is in, and where it is in that chunk
+  fn chunk_coordinates(position: [i32; 3]) -> ([i32; 3], usize) {
+    let chunk: [i32; 3] = position.map(|coordinate| coordinate.div_euclid(CHUNK_SIZE));
+    let [x, y, z]: [i32; 3] = position.map(|coordinate| coordinate.rem_euclid(CHUNK_SIZE));
+    let index: i32 = z + CHUNK_SIZE * (y + CHUNK_SIZE * x);
+    return (chunk, index as usize);
+  }
+  /// the cube at a set of coordinates
+  fn get(&self, position: [i32; 3]) -> CubeType {
+    let (chunk, index): ([i32; 3], usize) = World::chunk_coordinates(position);
+    return match self.chunks.get(&chunk) {
+      Some(cubes) => cubes[index],
+      None => CubeType::Air,
+    };
+  }
+  /// change the cube at a set of coordinates, making a new chunk if there isn't one there yet
+  fn set(&mut self, position: [i32; 3], cube_type: CubeType) {
+    let (chunk, index): ([i32; 3], usize) = World::chunk_coordinates(position);
+    // no need for a chunk full of air
+    if cube_type == CubeType::Air && !self.chunks.contains_key(&chunk) {
+      return;
+    }
+    self.chunks.entry(chunk).or_insert_with(|| vec![CubeType::Air; CHUNK_VOLUME])[index] = cube_type;
+  }
+  /// every cube that isn't air, with its coordinates, in no particular order
+  fn cubes(&self) -> impl Iterator<Item = ([i32; 3], CubeType)> + '_ {
+    return self.chunks.iter().flat_map(|(chunk, cubes)| {
+      return cubes.iter().enumerate().filter(|(_, cube_type)| **cube_type != CubeType::Air).map(move |(index, cube_type)| {
+        let index: i32 = index as i32;
+        let local: [i32; 3] = [index / (CHUNK_SIZE * CHUNK_SIZE), (index / CHUNK_SIZE) % CHUNK_SIZE, index % CHUNK_SIZE];
+        let position: [i32; 3] = [0, 1, 2].map(|axis| chunk[axis] * CHUNK_SIZE + local[axis]);
+        return (position, *cube_type);
+      });
+    });
+  }
+  /// the smallest box (min included, max excluded) with every cube that isn't air in it,
+  /// or nothing if the world is empty
+  fn bounds(&self) -> Option<([i32; 3], [i32; 3])> {
+    let mut bounds: Option<([i32; 3], [i32; 3])> = None;
+    for (position, _) in self.cubes() {
+      let (min, max): ([i32; 3], [i32; 3]) = bounds.unwrap_or((position, position.map(|coordinate| coordinate + 1)));
+      bounds = Some((
+        [0, 1, 2].map(|axis| min[axis].min(position[axis])),
+        [0, 1, 2].map(|axis| max[axis].max(position[axis] + 1)),
+      ));
+    }
+    return bounds;
+  }
+}
 
-  let contents: String = std::fs::read_to_string(path)
+/// read a world from a file, in either of the formats `save_world` has written over time.
+///
+/// files starting with WORLD_FILE_MAGIC are binary, see `save_world`. anything else is text:
+/// the first line is the box the world fills, as `min_x min_y min_z size_x size_y size_z`, then
+/// comes a digit for every cube in it (0 air, 1 grass, 2 stone, 3 wood, 4 dirt), x first, then y, then z.
+/// files from before there was a first line are just the digits, for a cube shaped world starting at 0, 0, 0
+/// (like the ones init_world.py used to make)
+fn load_world(path: &str) -> World {
+  let contents: Vec<u8> = std::fs::read(path)
     .expect("Should have been able to read the file, or not idk");
 
-  for char in contents.as_bytes() {
-    match char {
+  let (min, size, cubes): ([i32; 3], [i32; 3], Vec<CubeType>) = if contents.starts_with(WORLD_FILE_MAGIC) {
+    read_binary_world(&contents[WORLD_FILE_MAGIC.len()..])
+  } else {
+    read_text_world(&contents)
+  };
+  if size.iter().any(|side| *side < 0) || cubes.len() != size.iter().map(|side| *side as usize).product() {
+    panic!("save file doesn't have as many cubes as its size says.");
+  }
+
+  let mut world: World = World::new();
+  for (index, cube_type) in cubes.into_iter().enumerate() {
+    let index: i32 = index as i32;
+    let position: [i32; 3] = [
+      min[0] + index / (size[1] * size[2]),
+      min[1] + (index / size[2]) % size[1],
+      min[2] + index % size[2],
+    ];
+    world.set(position, cube_type);
+  }
+
+  return world
+}
+/// the box and cubes of a world saved as digits, with or without the first line
+fn read_text_world(contents: &[u8]) -> ([i32; 3], [i32; 3], Vec<CubeType>) {
+  let contents: &str = std::str::from_utf8(contents).expect("invalid element in save file.").trim_end();
+
+  let (min, size, cubes): ([i32; 3], [i32; 3], &str) = match contents.split_once('\n') {
+    Some((header, cubes)) => {
+      let numbers: Vec<i32> = header.split_whitespace().map(|number| number.parse().expect("invalid header in save file.")).collect();
+      if numbers.len() != 6 {
+        panic!("invalid header in save file.");
+      }
+      ([numbers[0], numbers[1], numbers[2]], [numbers[3], numbers[4], numbers[5]], cubes)
+    }
+    None => {
+      let side: i32 = (contents.len() as f64).cbrt().round() as i32;
+      ([0; 3], [side; 3], contents)
+    }
+  };
+
+  let cubes: Vec<CubeType> = cubes.bytes().map(|char| {
+    return match char {
 
-      b'0' => { cubes.push(CubeType::Air)   }
-      b'1' => { cubes.push(CubeType::Grass) }
-      b'2' => { cubes.push(CubeType::Stone) }
-      b'3' => { cubes.push(CubeType::Wood)  }
+      b'0' => { CubeType::Air   }
+      b'1' => { CubeType::Grass }
+      b'2' => { CubeType::Stone }
+      b'3' => { CubeType::Wood  }
+      b'4' => { CubeType::Dirt  }
 
       _ => {
         panic!("invalid element in save file.");
       }
+    };
+  }).collect();
+  return (min, size, cubes);
+}
+/// the box and cubes of a world saved by `save_world`, everything after WORLD_FILE_MAGIC
+fn read_binary_world(contents: &[u8]) -> ([i32; 3], [i32; 3], Vec<CubeType>) {
+  let mut bytes = contents.iter().copied();
+  let mut next_byte = || -> u8 {
+    return bytes.next().expect("save file ends too early.");
+  };
+
+  let version: u8 = next_byte();
+  if version > WORLD_FILE_VERSION {
+    panic!("save file is from a newer version ({version}), this one only reads up to {WORLD_FILE_VERSION}.");
+  }
+  // versions start at 1, there never was a 0
+  if version == 0 {
+    panic!("invalid version in save file.");
+  }
+  let mut next_i32 = || -> i32 {
+    return i32::from_le_bytes([0; 4].map(|_| next_byte()));
+  };
+  let min: [i32; 3] = [0; 3].map(|_| next_i32());
+  let size: [i32; 3] = [0; 3].map(|_| next_i32());
+
+  let palette: Vec<CubeType> = (0..next_byte()).map(|_| {
+    let name: Vec<u8> = (0..next_byte()).map(|_| next_byte()).collect();
+    return CUBE_TYPES.into_iter().find(|cube_type| cube_type.name().as_bytes() == name)
+      .unwrap_or_else(|| panic!("unknown cube {:?} in save file.", String::from_utf8_lossy(&name)));
+  }).collect();
+
+  let volume: usize = size.iter().try_fold(1, |volume: usize, side| volume.checked_mul((*side).max(0) as usize))
+    .expect("save file doesn't have as many cubes as its size says.");
+  let palette_type = |index: u32| -> CubeType {
+    return *palette.get(index as usize).expect("invalid element in save file.");
+  };
+  // the size comes from the file, so it can't be trusted to allocate that much up front
+  let mut cubes: Vec<CubeType> = Vec::new();
+  match next_byte() {
+    WORLD_FILE_RUNS => {
+      while cubes.len() < volume {
+        // the length is 7 bits at a time, the highest bit says if more are coming
+        let mut length: usize = 0;
+        let mut shift: u32 = 0;
+        loop {
+          let byte: u8 = next_byte();
+          if shift >= usize::BITS {
+            panic!("invalid run length in save file.");
+          }
+          length |= ((byte & 0x7f) as usize) << shift;
+          shift += 7;
+          if byte & 0x80 == 0 {
+            break;
+          }
+        }
+        if length > volume - cubes.len() {
+          panic!("save file doesn't have as many cubes as its size says.");
+        }
+        cubes.extend(std::iter::repeat_n(palette_type(next_byte() as u32), length));
+      }
+    }
+    WORLD_FILE_PACKED => {
+      let bits: u32 = palette_index_bits(palette.len());
+      // bits read but not used yet, lowest first
+      let mut buffer: u32 = 0;
+      let mut buffered: u32 = 0;
+      for _ in 0..volume {
+        while buffered < bits {
+          buffer |= (next_byte() as u32) << buffered;
+          buffered += 8;
+        }
+        cubes.push(palette_type(buffer & ((1 << bits) - 1)));
+        buffer >>= bits;
+        buffered -= bits;
+      }
+    }
+    encoding => {
+      panic!("unknown encoding {encoding} in save file.");
+    }
+  }
+  return (min, size, cubes);
+}
+/// how many bits it takes to write any index into a palette this long
+fn palette_index_bits(palette_length: usize) -> u32 {
+  return (usize::BITS - palette_length.saturating_sub(1).leading_zeros()).max(1);
+}
+/// write a world to a file, in binary:
+///
+/// - WORLD_FILE_MAGIC, then WORLD_FILE_VERSION as 1 byte
+/// - the box the world fills, as `min_x min_y min_z size_x size_y size_z`, 4 byte little endian each
+/// - the palette: how many cube types there are, then for each one the length of its name and the name
+/// - how the cubes are written, as 1 byte, then the cubes in the box (x first, then y, then z) as
+///   indices into the palette. whichever of these is smaller:
+///   - WORLD_FILE_RUNS: runs of the same cube, as the length of the run (7 bits per byte, with the
+///     highest bit set on all but the last byte) and then the index
+///   - WORLD_FILE_PACKED: just the indices, with as few bits as the palette needs, lowest bits first
+fn save_world(path: &str, world: &World) -> std::io::Result<()> {
+  let (min, max): ([i32; 3], [i32; 3]) = world.bounds().unwrap_or(([0; 3], [0; 3]));
+  let size: [i32; 3] = [0, 1, 2].map(|axis| max[axis] - min[axis]);
+
+  let mut palette: Vec<CubeType> = Vec::new();
+  let mut indices: Vec<u8> = Vec::new();
+  for x in min[0]..max[0] {
+    for y in min[1]..max[1] {
+      for z in min[2]..max[2] {
+        let cube_type: CubeType = world.get([x, y, z]);
+        indices.push(match palette.iter().position(|palette_type| *palette_type == cube_type) {
+          Some(index) => index as u8,
+          None => {
+            palette.push(cube_type);
+            (palette.len() - 1) as u8
+          }
+        });
+      }
+    }
+  }
+
+  let mut runs: Vec<u8> = vec![WORLD_FILE_RUNS];
+  for run in indices.chunk_by(|a, b| a == b) {
+    let mut length: usize = run.len();
+    while length >= 0x80 {
+      runs.push((length & 0x7f) as u8 | 0x80);
+      length >>= 7;
+    }
+    runs.push(length as u8);
+    runs.push(run[0]);
+  }
+  let mut packed: Vec<u8> = vec![WORLD_FILE_PACKED];
+  let bits: u32 = palette_index_bits(palette.len());
+  let mut buffer: u32 = 0;
+  let mut buffered: u32 = 0;
+  for index in indices {
+    buffer |= (index as u32) << buffered;
+    buffered += bits;
+    while buffered >= 8 {
+      packed.push(buffer as u8);
+      buffer >>= 8;
+      buffered -= 8;
+    }
+  }
+  if buffered > 0 {
+    packed.push(buffer as u8);
+  }
+
+  let mut contents: Vec<u8> = WORLD_FILE_MAGIC.to_vec();
+  contents.push(WORLD_FILE_VERSION);
+  for number in min.into_iter().chain(size) {
+    contents.extend(number.to_le_bytes());
+  }
+  contents.push(palette.len() as u8);
+  for cube_type in palette {
+    contents.push(cube_type.name().len() as u8);
+    contents.extend(cube_type.name().bytes());
+  }
+  contents.extend(if runs.len() < packed.len() { runs } else { packed });
+  // write next to it first and then swap it in, so quitting halfway through a save can't leave half a world behind
+  let temporary_path: String = format!("{path}.tmp");
+  std::fs::write(&temporary_path, contents)?;
+  return std::fs::rename(&temporary_path, path);
+}
+
+/// where to put the player in a world: on top of whatever is at 0, 0
+fn spawn_position(world: &World) -> Vector3 {
+  let (min, max): ([i32; 3], [i32; 3]) = world.bounds().unwrap_or(([0; 3], [0; 3]));
+  let ground: i32 = (min[1]..max[1]).rev().find(|y| world.get([0, *y, 0]) != CubeType::Air).map(|y| y + 1).unwrap_or(max[1]);
+  return Vector3 { x: 0.5, y: ground as f32 + EYE_HEIGHT, z: 0.5 };
+}
+
+/// a well mixed number made from a seed and 2 coordinates, always the same for the same input
+fn hash(seed: u32, x: i32, y: i32) -> u32 {
+  // the finalizer of murmur3, once per input so nearby coordinates end up far apart
+  let mix = |mut hash: u32| -> u32 {
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85ebca6b);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xc2b2ae35);
+    hash ^= hash >> 16;
+    return hash;
+  };
+  return mix(mix(mix(seed) ^ x as u32) ^ y as u32);
+}
+
+/// smooth random looking bumps (Perlin's gradient noise), about 1 bump per unit, between about -0.7 and 0.7.
+///
+/// every corner of the unit grid gets a random direction, and a point gets the slopes of its 4
+/// corners towards it, smoothly blended together
+fn gradient_noise(seed: u32, x: f32, y: f32) -> f32 {
+  let (cell_x, cell_y): (f32, f32) = (x.floor(), y.floor());
+  // where in the cell the point is
+  let (offset_x, offset_y): (f32, f32) = (x - cell_x, y - cell_y);
+  let slope = |corner_x: f32, corner_y: f32| -> f32 {
+    let angle: f32 = hash(seed, (cell_x + corner_x) as i32, (cell_y + corner_y) as i32) as f32 / u32::MAX as f32 * 2.0 * PI;
+    return angle.cos() * (offset_x - corner_x) + angle.sin() * (offset_y - corner_y);
+  };
+  // 6t⁵ - 15t⁴ + 10t³, flat at both ends so the cells join up without creases
+  let fade = |t: f32| -> f32 {
+    return t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+  };
+  let (blend_x, blend_y): (f32, f32) = (fade(offset_x), fade(offset_y));
+  let bottom: f32 = slope(0.0, 0.0) + (slope(1.0, 0.0) - slope(0.0, 0.0)) * blend_x;
+  let top: f32 = slope(0.0, 1.0) + (slope(1.0, 1.0) - slope(0.0, 1.0)) * blend_x;
+  return bottom + (top - bottom) * blend_y;
+}
+
+/// how many cubes high the terrain is at x, z
+fn terrain_height(seed: u32, x: i32, z: i32) -> i32 {
+  let mut noise: f32 = 0.0;
+  let mut amplitude: f32 = 1.0;
+  let mut frequency: f32 = 1.0 / TERRAIN_SCALE;
+  // every octave has its own noise, half as high and twice as dense as the one before
+  for octave in 0..TERRAIN_OCTAVES {
+    noise += gradient_noise(seed.wrapping_add(octave), x as f32 * frequency, z as f32 * frequency) * amplitude;
+    amplitude *= 0.5;
+    frequency *= 2.0;
+  }
+  // always at least one cube, so there's never a hole to fall through
+  return ((TERRAIN_HEIGHT + noise * TERRAIN_AMPLITUDE).round() as i32).max(1);
+}
+
+/// fill a chunk with generated terrain: stone from y = 0 up, then DIRT_DEPTH cubes of dirt,
+/// then grass on top. the same seed always gives the same terrain, whichever order chunks get generated in
+fn generate_chunk(world: &mut World, seed: u32, chunk: [i32; 3]) {
+  let chunk_min: [i32; 3] = chunk.map(|coordinate| coordinate * CHUNK_SIZE);
+  for x in chunk_min[0]..chunk_min[0] + CHUNK_SIZE {
+    for z in chunk_min[2]..chunk_min[2] + CHUNK_SIZE {
+      let height: i32 = terrain_height(seed, x, z);
+      for y in chunk_min[1].max(0)..(chunk_min[1] + CHUNK_SIZE).min(height) {
+        let cube_type: CubeType = if y == height - 1 {
+          CubeType::Grass
+        } else if y >= height - 1 - DIRT_DEPTH {
+          CubeType::Dirt
+        } else {
+          CubeType::Stone
+        };
+        world.set([x, y, z], cube_type);
+      }
     }
+  }
+}
+
+/// a new world of NEW_WORLD_CHUNKS by NEW_WORLD_CHUNKS chunks of terrain, around 0, 0
+fn generate_world(seed: u32) -> World {
+  let mut world: World = World::new();
+  // the noise never adds up to more than TERRAIN_OCTAVES, so neither does the terrain get any higher
+  let highest: i32 = (TERRAIN_HEIGHT + TERRAIN_AMPLITUDE * TERRAIN_OCTAVES as f32) as i32;
+  for chunk_x in -NEW_WORLD_CHUNKS / 2..NEW_WORLD_CHUNKS - NEW_WORLD_CHUNKS / 2 {
+    for chunk_z in -NEW_WORLD_CHUNKS / 2..NEW_WORLD_CHUNKS - NEW_WORLD_CHUNKS / 2 {
+      for chunk_y in 0..=highest.div_euclid(CHUNK_SIZE) {
+        generate_chunk(&mut world, seed, [chunk_x, chunk_y, chunk_z]);
+      }
+    }
+  }
+  return world;
+}
+
+/// 3D triangle -> 2D triangles (to be put on screen)
+///
+/// the triangle is thrown away if it's entirely out of the view frustum, and clipped against the
+/// near plane if it goes behind the camera, which can split it in two.
+fn render_triangle(triangle: Triangle3D, view_projection: Matrix4, screen_size_x: usize, screen_size_y: usize) -> Vec<RenderedTriangle2D> {
+  let mut rendered_triangles: Vec<RenderedTriangle2D> = Vec::new();
 
+  let clip_vertices: Vec<ClipVertex> = vec![
+    ClipVertex { position: to_clip_space(triangle.a, view_projection), ambient_occlusion: triangle.ambient_occlusion[0] },
+    ClipVertex { position: to_clip_space(triangle.b, view_projection), ambient_occlusion: triangle.ambient_occlusion[1] },
+    ClipVertex { position: to_clip_space(triangle.c, view_projection), ambient_occlusion: triangle.ambient_occlusion[2] },
+  ];
+  if outside_frustum(&clip_vertices) {
+    return rendered_triangles;
   }
 
-  return cubes
+  // 0, 3 or 4 vertices, turn them back into triangles as a fan around the first one
+  let polygon: Vec<ClipVertex> = clip_near_plane(&clip_vertices);
+  for i in 1..polygon.len().saturating_sub(1) {
+    let (vertex_a, depth_a) = clip_to_screen(polygon[0].position, screen_size_x, screen_size_y);
+    let (vertex_b, depth_b) = clip_to_screen(polygon[i].position, screen_size_x, screen_size_y);
+    let (vertex_c, depth_c) = clip_to_screen(polygon[i + 1].position, screen_size_x, screen_size_y);
+    rendered_triangles.push(RenderedTriangle2D {
+      triangle: Triangle2D { a: vertex_a, b: vertex_b, c: vertex_c, color: triangle.color },
+      depth_a,
+      depth_b,
+      depth_c,
+      ambient_occlusion: [polygon[0].ambient_occlusion, polygon[i].ambient_occlusion, polygon[i + 1].ambient_occlusion],
+    });
+  }
+  return rendered_triangles;
+}
+
+/// whether all the vertices are on the outer side of the same frustum plane.
+///
+/// such a polygon can't be seen at all. polygons poking out of the sides are still kept,
+/// the rasterizer only walks the part that's on screen anyway
+fn outside_frustum(clip_vertices: &[ClipVertex]) -> bool {
+  let planes: [fn(&Vector4) -> bool; 6] = [
+    |vertex| vertex.x < -vertex.w, // left
+    |vertex| vertex.x >  vertex.w, // right
+    |vertex| vertex.y < -vertex.w, // bottom
+    |vertex| vertex.y >  vertex.w, // top
+    |vertex| vertex.z <  0.0,      // near
+    |vertex| vertex.z >  vertex.w, // far
+  ];
+  return planes.iter().any(|outside| clip_vertices.iter().all(|vertex| outside(&vertex.position)));
 }
 
-fn render_triangle(triangle: Triangle3D, camera_position: Vector3, camera_rotation_vertical: f32, camera_rotation_horizontal: f32) -> (Triangle2D, f32) {
-  let (vertex_a, depth_a) = render_vertex(triangle.a, camera_position, camera_rotation_vertical, camera_rotation_horizontal);
-  let (vertex_b, _) = render_vertex(triangle.b, camera_position, camera_rotation_vertical, camera_rotation_horizontal);
-  let (vertex_c, _) = render_vertex(triangle.c, camera_position, camera_rotation_vertical, camera_rotation_horizontal);
+/// cut off the part of a polygon that is in front of the near plane (Sutherland-Hodgman).
+///
+/// in clip space the near plane is z = 0, so after this every vertex has a depth of at least NEAR_PLANE
+/// and can safely go through the perspective divide
+fn clip_near_plane(clip_vertices: &[ClipVertex]) -> Vec<ClipVertex> {
+  let mut clipped: Vec<ClipVertex> = Vec::new();
+  for (i, current) in clip_vertices.iter().enumerate() {
+    let next: &ClipVertex = &clip_vertices[(i + 1) % clip_vertices.len()];
+    let current_inside: bool = current.position.z >= 0.0;
+    let next_inside: bool = next.position.z >= 0.0;
+
+    if current_inside {
+      clipped.push(*current);
+    }
+    // the edge crosses the plane, add the crossing point
+    if current_inside != next_inside {
+      let t: f32 = current.position.z / (current.position.z - next.position.z);
+      clipped.push(ClipVertex {
+        position: vector4_lerp(current.position, next.position, t),
+        ambient_occlusion: current.ambient_occlusion + (next.ambient_occlusion - current.ambient_occlusion) * t,
+      });
+    }
+  }
+  return clipped;
+}
 
-  let test_colour: CustomColor = triangle.color;
+/// a vertex in clip space, along with what gets interpolated across the triangle
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ClipVertex {
+  position: Vector4,
+  ambient_occlusion: f32,
+}
 
-  // again, imprecise depth but whatever, good enough
-  return (Triangle2D { a: vertex_a, b: vertex_b, c: vertex_c, color: test_colour }, depth_a)
+/// world space -> clip space
+fn to_clip_space(vertex: Vector3, view_projection: Matrix4) -> Vector4 {
+  return view_projection.transform(Vector4 { x: vertex.x, y: vertex.y, z: vertex.z, w: 1.0 });
 }
 
-/// 3D point -> 2D point (to be put on screen)
-fn render_vertex(vertex: Vector3, camera_position: Vector3, camera_rotation_vertical: f32, camera_rotation_horizontal: f32) -> (Vector2, f32) {
+/// clip space -> NDC -> screen space.
+///
+/// also returns the depth of the vertex (its distance along the camera's forward axis).
+/// no clipping is done here, so the vertex has to be in front of the camera
+fn clip_to_screen(clip: Vector4, screen_size_x: usize, screen_size_y: usize) -> (Vector2, f32) {
+  // the projection matrix copies the view space depth into w
+  let depth: f32 = clip.w;
 
-  let position_on_vertical_plane: Vector2 = Vector2   { x: vertex.y, y: vertex.z };
-  let position_on_horizontal_plane: Vector2 = Vector2 { x: vertex.x, y: vertex.z };  
+  // perspective divide
+  let ndc: Vector2 = Vector2 { x: clip.x / clip.w, y: clip.y / clip.w };
 
-  let position_camera_vertical_plane: Vector2 =  Vector2   { x: camera_position.y, y: camera_position.z };
-  let position_camera_horizontal_plane: Vector2 =  Vector2 { x: camera_position.x, y: camera_position.z };
+  return (ndc_to_screen(ndc, screen_size_x, screen_size_y), depth);
+}
 
-  let (screen_x, depth_x) = project_2d_to_1d(position_on_vertical_plane, position_camera_vertical_plane, camera_rotation_vertical);
-  let (screen_y, depth_y) = project_2d_to_1d(position_on_horizontal_plane, position_camera_horizontal_plane, camera_rotation_horizontal);
-  // imprecise depth calculation, but probs decent enough for a block game
-  return (Vector2 { x: screen_x, y: screen_y }, depth_x+depth_y);
+/// NDC ([-1; 1], y up) -> screen coordinates (in pixels, origin at the top left).
+///
+/// keep in mind the screen's x is vertical and y is horizontal
+fn ndc_to_screen(ndc: Vector2, size_x: usize, size_y: usize) -> Vector2 {
+  let screen_x: f32 = (1.0 - ndc.y) * 0.5 * size_x as f32;
+  let screen_y: f32 = (ndc.x + 1.0) * 0.5 * size_y as f32;
+  return Vector2 { x: screen_x, y: screen_y };
 }
 
-/// point on 2D plane -> point on 1D axis
-fn project_2d_to_1d(vertex: Vector2, camera_position: Vector2, camera_rotation: f32) -> (f32, f32){
-  //      this line represents the screen we project on
-  //                          |
-  //            ----\         |
-  //            |    ----\   \'/    hypotenuse = camera_vertex_distance
-  //            |         ----\
-  // relative_y |             |----\
-  //            |    screen_y |    ----\
-  //            |             |          ----\
-  //            -----------------------------------X  camera_position
-  //                           <---CAMERA_DISTANCE--->
-  //            <---------------depth---------------->
+/// the full world space -> clip space transformation for a camera
+fn camera_matrix(camera_position: Vector3, camera_rotation_vertical: f32, camera_rotation_horizontal: f32, aspect_ratio: f32) -> Matrix4 {
+  let view: Matrix4 = view_matrix(camera_position, camera_rotation_vertical, camera_rotation_horizontal);
+  let projection: Matrix4 = projection_matrix(FIELD_OF_VIEW, aspect_ratio, NEAR_PLANE, FAR_PLANE);
+  return projection.multiply(view);
+}
 
-  // distance between camera and the vertex
-  let camera_vertex_distance: f32 = vector2_distance(camera_position.clone(), vertex.clone());
-  // find the vector that connects camera and vertex
-  let camera_vertex_distance_vector: Vector2 = Vector2 { x: vertex.x - camera_position.x, y: vertex.y - camera_position.y };
-  // world angle of vertex relative to camera
-  let vertex_relative_angle: f32 = f32::atan(camera_vertex_distance_vector.x/camera_vertex_distance_vector.y);
-  // relative to camera angle of vertex relative to camera
-  let vertex_camera_angle: f32 = camera_rotation - vertex_relative_angle;
-  
-  // find depth
-  let depth: f32 = f32::cos(vertex_camera_angle) * camera_vertex_distance;
-  // find relative_y
-  let relative_y: f32 = f32::sin(vertex_camera_angle) * camera_vertex_distance;
-  // Now we apply Thales' Theorem to find screen_y
-  let screen_y: f32 = (CAMERA_DISTANCE * relative_y) / (depth - CAMERA_DISTANCE);
-  return (screen_y * SIZE_MULTIPLIER, depth);
+/// world space -> view space (camera at the origin, looking down +z, y up, x right)
+fn view_matrix(camera_position: Vector3, camera_rotation_vertical: f32, camera_rotation_horizontal: f32) -> Matrix4 {
+  let (sin_h, cos_h) = f32::sin_cos(camera_rotation_horizontal);
+
+  // camera basis
+  let forward: Vector3 = angle_couple_to_vector3(camera_rotation_horizontal, camera_rotation_vertical);
+  let right: Vector3 = Vector3 { x: cos_h, y: 0.0, z: -sin_h };
+  let up: Vector3 = vector3_cross(forward, right);
+
+  // rotate into the camera basis, after moving the camera to the origin
+  return Matrix4 { m: [
+    [right.x,   right.y,   right.z,   -vector3_dot(right, camera_position)  ],
+    [up.x,      up.y,      up.z,      -vector3_dot(up, camera_position)     ],
+    [forward.x, forward.y, forward.z, -vector3_dot(forward, camera_position)],
+    [0.0,       0.0,       0.0,       1.0                                   ],
+  ]};
 }
 
+/// view space -> clip space
+///
+/// x and y end up in [-w; w] when on screen, z in [0; w] between the near and far planes,
+/// and w holds the view space depth.
+fn projection_matrix(field_of_view: f32, aspect_ratio: f32, near: f32, far: f32) -> Matrix4 {
+  let focal_length: f32 = 1.0 / f32::tan(field_of_view / 2.0);
+  let depth_scale: f32 = far / (far - near);
+  return Matrix4 { m: [
+    [focal_length / aspect_ratio, 0.0,          0.0,         0.0                ],
+    [0.0,                         focal_length, 0.0,         0.0                ],
+    [0.0,                         0.0,          depth_scale, -near * depth_scale],
+    [0.0,                         0.0,          1.0,         0.0                ],
+  ]};
+}
 
 /// Calculate the distance between two 3D points.
 /// 
 /// returns `sqrt((vec2.x - vec1.x)² + (vec2.y - vec1.y)² + (vec2.z - vec1.z)²)`
+#[cfg(test)]
 fn vector3_distance(vec1: Vector3, vec2: Vector3) -> f32 {
 
   let x_dist_sq: f32 = f32::powf(vec2.x - vec1.x, 2.0);
@@ -485,16 +1575,19 @@ fn vector3_distance(vec1: Vector3, vec2: Vector3) -> f32 {
   return distance;
 }
 
-/// Calculate the distance between two 2D points.
-/// 
-/// returns `sqrt((vec2.x - vec1.x)² + (vec2.y - vec1.y)²)`
-fn vector2_distance(vec1: Vector2, vec2: Vector2) -> f32 {
+/// `[x, y, z]` -> Vector3, handy when the axis to work on is picked at runtime
+fn array_to_vector3(array: [f32; 3]) -> Vector3 {
+  return Vector3 { x: array[0], y: array[1], z: array[2] };
+}
 
-  let x_dist_sq: f32 = f32::powf(vec2.x - vec1.x, 2.0);
-  let y_dist_sq: f32 = f32::powf(vec2.y - vec1.y, 2.0);
+/// Add two vectors together
+fn vector3_add(vec1: Vector3, vec2: Vector3) -> Vector3 {
+  return Vector3 { x: vec1.x + vec2.x, y: vec1.y + vec2.y, z: vec1.z + vec2.z };
+}
 
-  let distance = f32::sqrt(x_dist_sq + y_dist_sq);
-  return distance;
+/// Subtract `vec2` from `vec1`, the vector pointing from `vec2` to `vec1`
+fn vector3_subtract(vec1: Vector3, vec2: Vector3) -> Vector3 {
+  return Vector3 { x: vec1.x - vec2.x, y: vec1.y - vec2.y, z: vec1.z - vec2.z };
 }
 
 /// Calculate the dot product between two vectors
@@ -503,6 +1596,32 @@ fn vector3_dot(vec1: Vector3, vec2: Vector3) -> f32 {
   return dot_product
 }
 
+/// Linear interpolation between two vectors, `t = 0` gives `vec1` and `t = 1` gives `vec2`
+fn vector4_lerp(vec1: Vector4, vec2: Vector4, t: f32) -> Vector4 {
+  return Vector4 {
+    x: vec1.x + (vec2.x - vec1.x) * t,
+    y: vec1.y + (vec2.y - vec1.y) * t,
+    z: vec1.z + (vec2.z - vec1.z) * t,
+    w: vec1.w + (vec2.w - vec1.w) * t,
+  };
+}
+
+/// Scale a vector to a length of 1
+fn vector3_normalize(vec: Vector3) -> Vector3 {
+  let length: f32 = f32::sqrt(vector3_dot(vec, vec));
+  return Vector3 { x: vec.x / length, y: vec.y / length, z: vec.z / length };
+}
+
+/// Calculate the cross product between two vectors
+fn vector3_cross(vec1: Vector3, vec2: Vector3) -> Vector3 {
+  let cross_product: Vector3 = Vector3 {
+    x: vec1.y * vec2.z - vec1.z * vec2.y,
+    y: vec1.z * vec2.x - vec1.x * vec2.z,
+    z: vec1.x * vec2.y - vec1.y * vec2.x,
+  };
+  return cross_product;
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 struct Vector3 {
   x : f32,
@@ -516,6 +1635,44 @@ struct Vector2 {
   y : f32,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Vector4 {
+  x : f32,
+  y : f32,
+  z : f32,
+  w : f32,
+}
+
+/// 4x4 matrix, stored row by row
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Matrix4 {
+  m: [[f32; 4]; 4],
+}
+impl Matrix4 {
+  /// returns `self * other`
+  fn multiply(&self, other: Matrix4) -> Matrix4 {
+    let mut result: Matrix4 = Matrix4 { m: [[0.0; 4]; 4] };
+    for row in 0..4 {
+      for column in 0..4 {
+        for i in 0..4 {
+          result.m[row][column] += self.m[row][i] * other.m[i][column];
+        }
+      }
+    }
+    return result;
+  }
+  /// returns `self * vector`
+  fn transform(&self, vector: Vector4) -> Vector4 {
+    let m = self.m;
+    return Vector4 {
+      x: m[0][0] * vector.x + m[0][1] * vector.y + m[0][2] * vector.z + m[0][3] * vector.w,
+      y: m[1][0] * vector.x + m[1][1] * vector.y + m[1][2] * vector.z + m[1][3] * vector.w,
+      z: m[2][0] * vector.x + m[2][1] * vector.y + m[2][2] * vector.z + m[2][3] * vector.w,
+      w: m[3][0] * vector.x + m[3][1] * vector.y + m[3][2] * vector.z + m[3][3] * vector.w,
+    };
+  }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 struct Triangle3D {
   a:      Vector3,
@@ -523,6 +1680,8 @@ struct Triangle3D {
   c:      Vector3,
   color:  CustomColor,
   normal: Vector3,
+  /// light multiplier at a, b and c (1 = not occluded), interpolated across the triangle
+  ambient_occlusion: [f32; 3],
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -534,71 +1693,599 @@ struct Triangle2D {
   //parent_cube_idex: usize,
 }
 impl Triangle2D {
-  /// Check if a triangle contains a certain point
-  fn contains(&self, point: Vector2) -> bool {
-    // say ABC = triangle and P = point:
-    // test if area ABC = area ABP + area ACP + area BCP
-    let real_triangle_area = triangle_area(self.clone());
+  /// find every pixel whose center is covered by the triangle.
+  ///
+  /// only the triangle's bounding box (clamped to the screen) is walked. pixels exactly on an edge
+  /// follow the top-left rule, so pixels on an edge shared by two triangles are drawn exactly once.
+  fn rasterize(&self, size_x: usize, size_y: usize) -> Vec<Fragment> {
+    let mut fragments: Vec<Fragment> = Vec::new();
+
+    // make the winding consistent so the inside of the triangle is always on the positive side of its edges
+    let (a, b, c): (Vector2, Vector2, Vector2) = if edge_function(self.a, self.b, self.c) < 0.0 {
+      (self.a, self.c, self.b)
+    } else {
+      (self.a, self.b, self.c)
+    };
+    let area: f32 = edge_function(a, b, c);
+    // degenerate triangles cover nothing
+    if area <= 0.0 || !area.is_finite() {
+      return fragments;
+    }
 
-    let mut area_sum: f32 = 0.0;
-    area_sum += triangle_area(Triangle2D { a: self.a, b: self.b, c: point, color: CustomColor {r: 0, g: 0, b: 0} });
-    area_sum += triangle_area(Triangle2D { a: self.a, b: self.c, c: point, color: CustomColor {r: 0, g: 0, b: 0} });
-    area_sum += triangle_area(Triangle2D { a: self.b, b: self.c, c: point, color: CustomColor {r: 0, g: 0, b: 0} });
+    // bounding box, clamped to the screen
+    let min_x: f32 = f32::max(f32::min(a.x, f32::min(b.x, c.x)).floor(), 0.0);
+    let max_x: f32 = f32::min(f32::max(a.x, f32::max(b.x, c.x)).ceil(), size_x as f32);
+    let min_y: f32 = f32::max(f32::min(a.y, f32::min(b.y, c.y)).floor(), 0.0);
+    let max_y: f32 = f32::min(f32::max(a.y, f32::max(b.y, c.y)).ceil(), size_y as f32);
+    if min_x >= max_x || min_y >= max_y {
+      return fragments;
+    }
 
-    return real_triangle_area == area_sum;
+    let top_left_bc: bool = is_top_left(b, c);
+    let top_left_ca: bool = is_top_left(c, a);
+    let top_left_ab: bool = is_top_left(a, b);
+
+    for x in (min_x as usize)..(max_x as usize) {
+      for y in (min_y as usize)..(max_y as usize) {
+        // sample at the center of the pixel
+        let pixel_center: Vector2 = Vector2 { x: x as f32 + 0.5, y: y as f32 + 0.5 };
+        let edge_bc: f32 = edge_function(b, c, pixel_center);
+        let edge_ca: f32 = edge_function(c, a, pixel_center);
+        let edge_ab: f32 = edge_function(a, b, pixel_center);
+
+        if covers(edge_bc, top_left_bc) && covers(edge_ca, top_left_ca) && covers(edge_ab, top_left_ab) {
+          // the edge function opposite a vertex is that vertex's weight (times the area)
+          let (weight_a, weight_b, weight_c): (f32, f32, f32) = (edge_bc / area, edge_ca / area, edge_ab / area);
+          // put the weights back in the original vertex order if we swapped b and c
+          let weights: Vector3 = if b == self.b {
+            Vector3 { x: weight_a, y: weight_b, z: weight_c }
+          } else {
+            Vector3 { x: weight_a, y: weight_c, z: weight_b }
+          };
+          fragments.push(Fragment { x, y, weights });
+        }
+      }
+    }
+    return fragments;
   }
 }
-/// calculate the area of a 2D triangle
-fn triangle_area(triangle: Triangle2D) -> f32 {
-  let ab: Vector2 = Vector2 { x: triangle.b.x - triangle.a.x, y: triangle.b.y - triangle.a.y };
-  let ac: Vector2 = Vector2 { x: triangle.c.x - triangle.a.x, y: triangle.c.y - triangle.a.y };
-  let cross_product: f32 = ab.x * ac.y - ab.y * ac.x;
-  let area = f32::abs(cross_product);
-  return area/2.0;
+/// a pixel covered by a triangle
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Fragment {
+  x: usize,
+  y: usize,
+  /// barycentric coordinates of the pixel center.
+  /// x, y and z are the weights of the triangle's a, b and c, they add up to 1
+  weights: Vector3,
+}
+/// twice the signed area of the triangle ABP.
+///
+/// positive on one side of AB, negative on the other and 0 on the line itself
+fn edge_function(a: Vector2, b: Vector2, point: Vector2) -> f32 {
+  return (b.x - a.x) * (point.y - a.y) - (b.y - a.y) * (point.x - a.x);
+}
+/// whether the edge AB of a positively wound triangle is a top or a left edge.
+///
+/// (remember x goes down the screen and y goes right)
+fn is_top_left(a: Vector2, b: Vector2) -> bool {
+  let edge: Vector2 = Vector2 { x: b.x - a.x, y: b.y - a.y };
+  let is_top: bool = edge.x == 0.0 && edge.y < 0.0;
+  let is_left: bool = edge.x > 0.0;
+  return is_top || is_left;
+}
+/// whether a pixel is on the inside of an edge, pixels right on the edge only count for top-left edges
+fn covers(edge_value: f32, top_left: bool) -> bool {
+  return edge_value > 0.0 || (edge_value == 0.0 && top_left);
 }
 
 struct Screen {
   pixels: Vec<Vec<CustomColor>>,
+  /// depth of whatever was drawn on each pixel, same layout as `pixels`
+  depth: Vec<Vec<f32>>,
   size_x: usize,
   size_y: usize,
 }
 impl Screen {
   fn init(&mut self) {
     let mut screen: Vec<Vec<CustomColor>> = Vec::new();
+    let mut depth: Vec<Vec<f32>> = Vec::new();
     for _ in 0..self.size_x {
       let mut x_row: Vec<CustomColor> = Vec::new();
+      let mut depth_x_row: Vec<f32> = Vec::new();
       for _ in 0..self.size_y {
-        x_row.push(CustomColor{r: 0, g: 0, b: 0});
+        x_row.push(SKY_COLOR);
+        // nothing drawn yet, so anything is in front
+        depth_x_row.push(f32::INFINITY);
       }
       screen.push(x_row);
+      depth.push(depth_x_row);
     }
     self.pixels = screen;
+    self.depth = depth;
+  }
+  /// colour a pixel, unless something closer has already been drawn on it
+  fn plot(&mut self, x: usize, y: usize, color: CustomColor, depth: f32) {
+    if depth < self.depth[x][y] {
+      self.depth[x][y] = depth;
+      self.pixels[x][y] = color;
+    }
+  }
+  /// group pixels into terminal characters, row by row
+  fn cells(&self, pixel_mode: PixelMode) -> Vec<Vec<Cell>> {
+    let mut cells: Vec<Vec<Cell>> = Vec::new();
+    match pixel_mode {
+      PixelMode::FullBlock => {
+        for x_row in self.pixels.iter() {
+          cells.push(x_row.iter().map(|pixel| Cell { foreground: *pixel, background: *pixel }).collect());
+        }
+      }
+      PixelMode::HalfBlock => {
+        // the top half of the character is the foreground, the bottom half the background
+        for x in (0..self.size_x).step_by(2) {
+          let mut cell_row: Vec<Cell> = Vec::new();
+          for y in 0..self.size_y {
+            let top_color: CustomColor = self.pixels[x][y];
+            // odd number of rows, the last bottom half is left black
+            let bottom_color: CustomColor = match self.pixels.get(x + 1) {
+              Some(x_row) => x_row[y],
+              None => CustomColor { r: 0, g: 0, b: 0 },
+            };
+            cell_row.push(Cell { foreground: top_color, background: bottom_color });
+          }
+          cells.push(cell_row);
+        }
+      }
+    }
+    return cells;
+  }
+}
+
+/// how many pixels fit in a terminal of `rows` by `columns` characters
+fn screen_size(rows: usize, columns: usize, pixel_mode: PixelMode) -> (usize, usize) {
+  // one extra line, so the cursor sitting after the last line doesn't scroll everything up
+  let image_rows: usize = rows.saturating_sub(1).max(1);
+  let (screen_size_x, screen_size_y): (usize, usize) = match pixel_mode {
+    PixelMode::FullBlock => (image_rows, columns / 2),
+    PixelMode::HalfBlock => (image_rows * 2, columns),
+  };
+  return (screen_size_x, screen_size_y.max(1));
+}
+
+/// draw the HOTBAR over the bottom of the screen: a row of squares in the colors of the cubes,
+/// with a white border around the selected one
+fn draw_hotbar(overlay: &mut Overlay, selected_slot: usize) {
+  // one pixel between slots, and under them
+  let width: usize = HOTBAR.len() * (HOTBAR_SLOT_SIZE + 1) - 1;
+  let top: usize = overlay.size_x.saturating_sub(HOTBAR_SLOT_SIZE + 1);
+  let left: usize = overlay.size_y.saturating_sub(width) / 2;
+  for (slot, cube_type) in HOTBAR.iter().enumerate() {
+    let slot_left: usize = left + slot * (HOTBAR_SLOT_SIZE + 1);
+    let border_color: CustomColor = if slot == selected_slot { HOTBAR_SELECTED_COLOR } else { HOTBAR_BORDER_COLOR };
+    overlay.fill_rectangle(top, slot_left, HOTBAR_SLOT_SIZE, HOTBAR_SLOT_SIZE, border_color, 1.0);
+    overlay.fill_rectangle(top + 1, slot_left + 1, HOTBAR_SLOT_SIZE - 2, HOTBAR_SLOT_SIZE - 2, cube_type.color(), 1.0);
+  }
+}
+
+/// a pixel of the overlay, `opacity` is how much it covers what's under it, from 0 to 1
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct OverlayPixel {
+  color: CustomColor,
+  opacity: f32,
+}
+
+/// the HUD: whatever gets drawn on top of the world (crosshair, text, panels), with the same layout
+/// as `Screen.pixels` so it lines up with the image
+struct Overlay {
+  /// nothing where the world shows through
+  pixels: Vec<Vec<Option<OverlayPixel>>>,
+  size_x: usize,
+  size_y: usize,
+}
+impl Overlay {
+  fn new(size_x: usize, size_y: usize) -> Overlay {
+    return Overlay { pixels: vec![vec![None; size_y]; size_x], size_x, size_y };
+  }
+  /// colour a rectangle of pixels, starting at row `x` and column `y`, whatever was there before.
+  /// the parts outside of the screen are left out
+  fn fill_rectangle(&mut self, x: usize, y: usize, size_x: usize, size_y: usize, color: CustomColor, opacity: f32) {
+    for row in self.pixels.iter_mut().skip(x).take(size_x) {
+      for pixel in row.iter_mut().skip(y).take(size_y) {
+        *pixel = Some(OverlayPixel { color, opacity });
+      }
+    }
+  }
+  /// a small plus in the middle of the screen, right where the player's reach goes
+  fn draw_crosshair(&mut self) {
+    let (center_x, center_y): (usize, usize) = (self.size_x / 2, self.size_y / 2);
+    self.fill_rectangle(center_x.saturating_sub(1), center_y, 3, 1, CROSSHAIR_COLOR, 1.0);
+    self.fill_rectangle(center_x, center_y.saturating_sub(1), 1, 3, CROSSHAIR_COLOR, 1.0);
+  }
+  /// write a line of text with its top left corner at row `x` and column `y`, see `glyph`
+  fn draw_text(&mut self, x: usize, y: usize, text: &str, color: CustomColor) {
+    for (index, character) in text.chars().enumerate() {
+      let character_y: usize = y + index * (GLYPH_WIDTH + 1);
+      for (row, bits) in glyph(character).iter().enumerate() {
+        for column in 0..GLYPH_WIDTH {
+          // leftmost pixel in the highest bit
+          if bits >> (GLYPH_WIDTH - 1 - column) & 1 == 1 {
+            self.fill_rectangle(x + row, character_y + column, 1, 1, color, 1.0);
+          }
+        }
+      }
+    }
+  }
+  /// lines of text on a panel, with its top left corner at row `x` and column `y`
+  fn draw_label(&mut self, x: usize, y: usize, lines: &[String]) {
+    let longest: usize = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0);
+    // a pixel of border all around
+    let size_x: usize = lines.len() * (GLYPH_HEIGHT + 1) + 1;
+    let size_y: usize = longest * (GLYPH_WIDTH + 1) + 1;
+    self.fill_rectangle(x, y, size_x, size_y, PANEL_COLOR, PANEL_OPACITY);
+    for (index, line) in lines.iter().enumerate() {
+      self.draw_text(x + 1 + index * (GLYPH_HEIGHT + 1), y + 1, line, TEXT_COLOR);
+    }
+  }
+  /// put the overlay on top of the screen
+  fn composite(&self, screen: &mut Screen) {
+    for (overlay_row, screen_row) in self.pixels.iter().zip(screen.pixels.iter_mut()) {
+      for (overlay_pixel, screen_pixel) in overlay_row.iter().zip(screen_row.iter_mut()) {
+        if let Some(OverlayPixel { color, opacity }) = overlay_pixel {
+          *screen_pixel = mix_colors(*screen_pixel, *color, *opacity);
+        }
+      }
+    }
+  }
+}
+
+const GLYPH_WIDTH: usize = 3;
+const GLYPH_HEIGHT: usize = 5;
+
+/// the pixels of a character in a tiny 3 by 5 font, one row per number with the leftmost pixel
+/// in the highest bit. only capitals, lowercase letters get turned into them, and anything the
+/// font doesn't have is a question mark
+fn glyph(character: char) -> [u8; GLYPH_HEIGHT] {
+  return match character.to_ascii_uppercase() {
+    '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+    '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+    '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+    '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+    '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+    '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+    '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+    '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+    '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+    '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+    'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+    'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+    'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+    'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+    'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+    'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+    'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+    'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+    'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+    'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+    'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+    'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+    'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+    'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+    'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+    'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+    'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+    'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+    'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+    'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+    'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+    'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+    'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+    'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+    'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+    'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+    ' ' => [0b000, 0b000, 0b000, 0b000, 0b000],
+    '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+    ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+    ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+    '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+    '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+    '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+    '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+    '(' => [0b001, 0b010, 0b010, 0b010, 0b001],
+    ')' => [0b100, 0b010, 0b010, 0b010, 0b100],
+    '°' => [0b010, 0b101, 0b010, 0b000, 0b000],
+    _   => [0b111, 0b001, 0b010, 0b000, 0b010],
+  };
+}
+
+/// how pixels are turned into characters in the terminal
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PixelMode {
+  /// "██", each pixel is 2 characters wide
+  FullBlock,
+  /// "▀" with different foreground and background colors, each character is 2 pixels stacked on top of each other.
+  /// characters are about twice as tall as they are wide, so this gives roughly square pixels
+  HalfBlock,
+}
+
+/// what a single character of the terminal looks like
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Cell {
+  foreground: CustomColor,
+  background: CustomColor,
+}
+
+/// puts Screens on the terminal.
+///
+/// remembers what was drawn last frame, so that only the characters that changed are redrawn
+/// (instead of clearing the whole terminal and flickering)
+struct TerminalOutput {
+  pixel_mode: PixelMode,
+  color_mode: ColorMode,
+  /// what is currently on the terminal, empty if we don't know
+  previous_cells: Vec<Vec<Cell>>,
+  /// palette of the color mode (empty for true color), with each entry's Oklab coordinates
+  palette: Vec<(u8, [f32; 3])>,
+  /// palette entry already picked for each color, finding the nearest one every time is slow
+  palette_cache: HashMap<(u8, u8, u8), u8>,
+}
+impl TerminalOutput {
+  fn new(pixel_mode: PixelMode, color_mode: ColorMode) -> TerminalOutput {
+    let palette: Vec<(u8, [f32; 3])> = color_mode.palette().iter().map(|(index, color)| (*index, oklab(*color))).collect();
+    return TerminalOutput { pixel_mode, color_mode, previous_cells: Vec::new(), palette, palette_cache: HashMap::new() };
+  }
+  /// draw a screen on the terminal, in a single write.
+  ///
+  /// leaves the cursor on the line right below the image
+  fn present(&mut self, screen: &Screen) {
+    let frame: String = self.render_frame(screen);
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(frame.as_bytes()).expect("failed to write to the terminal");
+    stdout.flush().expect("failed to write to the terminal");
+  }
+  /// build everything that needs to be written to the terminal to go from the previous frame to this one
+  fn render_frame(&mut self, screen: &Screen) -> String {
+    let cells: Vec<Vec<Cell>> = screen.cells(self.pixel_mode);
+    let mut frame: String = String::new();
+
+    // first frame, or the size changed: start from a blank terminal
+    let same_size: bool = self.previous_cells.len() == cells.len()
+      && self.previous_cells.iter().zip(cells.iter()).all(|(previous_row, row)| previous_row.len() == row.len());
+    if !same_size {
+      frame.push_str("\x1b[2J");
+      self.previous_cells = Vec::new();
+    }
+
+    let (glyph, cell_width): (&str, usize) = match self.pixel_mode {
+      PixelMode::FullBlock => ("██", 2),
+      PixelMode::HalfBlock => ("▀", 1),
+    };
+
+    // colors the terminal is currently set to, so we only send them when they change
+    // (as escape sequences, different colors can end up as the same palette entry)
+    let mut current_foreground: String = String::new();
+    let mut current_background: String = String::new();
+
+    for (row_index, row) in cells.iter().enumerate() {
+      // whether the cursor is already right where the next character goes
+      let mut cursor_in_place: bool = false;
+      for (column_index, cell) in row.iter().enumerate() {
+        let unchanged: bool = same_size && self.previous_cells[row_index][column_index] == *cell;
+        if unchanged {
+          cursor_in_place = false;
+          continue;
+        }
+        if !cursor_in_place {
+          // terminal coordinates start at 1
+          frame.push_str(&format!("\x1b[{};{}H", row_index + 1, column_index * cell_width + 1));
+          cursor_in_place = true;
+        }
+        let foreground: String = self.color_escape(cell.foreground, true);
+        if current_foreground != foreground {
+          frame.push_str(&foreground);
+          current_foreground = foreground;
+        }
+        // full blocks don't show their background at all
+        if self.pixel_mode == PixelMode::HalfBlock {
+          let background: String = self.color_escape(cell.background, false);
+          if current_background != background {
+            frame.push_str(&background);
+            current_background = background;
+          }
+        }
+        frame.push_str(glyph);
+      }
+    }
+
+    // back to normal colors, cursor below the image, and get rid of whatever was printed there last frame
+    frame.push_str(&format!("\x1b[0m\x1b[{};1H\x1b[J", cells.len() + 1));
+
+    self.previous_cells = cells;
+    return frame;
+  }
+  /// escape sequence that sets the foreground (or background) color of the terminal
+  fn color_escape(&mut self, color: CustomColor, foreground: bool) -> String {
+    match self.color_mode {
+      ColorMode::TrueColor => {
+        let layer: u8 = if foreground { 38 } else { 48 };
+        return format!("\x1b[{};2;{};{};{}m", layer, color.r, color.g, color.b);
+      }
+      ColorMode::Xterm256 => {
+        let layer: u8 = if foreground { 38 } else { 48 };
+        return format!("\x1b[{};5;{}m", layer, self.nearest_palette_entry(color));
+      }
+      ColorMode::Basic16 => {
+        // 30-37 / 40-47 for the first 8 colors, 90-97 / 100-107 for their bright versions
+        let index: u8 = self.nearest_palette_entry(color);
+        let base: u8 = match (foreground, index < 8) {
+          (true, true)   => 30,
+          (true, false)  => 90 - 8,
+          (false, true)  => 40,
+          (false, false) => 100 - 8,
+        };
+        return format!("\x1b[{}m", base + index);
+      }
+    }
+  }
+  /// the palette entry that looks the closest to a color
+  fn nearest_palette_entry(&mut self, color: CustomColor) -> u8 {
+    let key: (u8, u8, u8) = (color.r, color.g, color.b);
+    if let Some(index) = self.palette_cache.get(&key) {
+      return *index;
+    }
+    let index: u8 = nearest_palette_entry(color, &self.palette);
+    self.palette_cache.insert(key, index);
+    return index;
+  }
+}
+/// find the palette entry that looks the closest to a color.
+///
+/// distances are measured in Oklab, where they match how different colors look to us
+/// much better than in RGB
+fn nearest_palette_entry(color: CustomColor, palette: &[(u8, [f32; 3])]) -> u8 {
+  let lab: [f32; 3] = oklab(color);
+  let mut nearest_index: u8 = 0;
+  let mut nearest_distance: f32 = f32::INFINITY;
+  for (index, entry) in palette {
+    let distance: f32 = (lab[0] - entry[0]).powi(2) + (lab[1] - entry[1]).powi(2) + (lab[2] - entry[2]).powi(2);
+    if distance < nearest_distance {
+      nearest_distance = distance;
+      nearest_index = *index;
+    }
+  }
+  return nearest_index;
+}
+/// sRGB -> Oklab (lightness, green/red, blue/yellow)
+fn oklab(color: CustomColor) -> [f32; 3] {
+  // sRGB -> linear RGB
+  let linear = |channel: u8| -> f32 {
+    let channel: f32 = channel as f32 / 255.0;
+    if channel <= 0.04045 {
+      return channel / 12.92;
+    }
+    return ((channel + 0.055) / 1.055).powf(2.4);
+  };
+  let (r, g, b): (f32, f32, f32) = (linear(color.r), linear(color.g), linear(color.b));
+
+  // linear RGB -> cone responses -> Oklab
+  let l: f32 = (0.4122215 * r + 0.5363325 * g + 0.05144599 * b).cbrt();
+  let m: f32 = (0.2119035 * r + 0.6806995 * g + 0.107397 * b).cbrt();
+  let s: f32 = (0.08830246 * r + 0.2817188 * g + 0.6299787 * b).cbrt();
+  return [
+    0.2104543 * l + 0.7936178 * m - 0.004072047 * s,
+    1.977998 * l - 2.428592 * m + 0.4505937 * s,
+    0.02590404 * l + 0.7827718 * m - 0.8086758 * s,
+  ];
+}
+
+/// how many colors the terminal can show
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ColorMode {
+  /// any 24 bit color
+  TrueColor,
+  /// the 256 colors of xterm
+  Xterm256,
+  /// the 16 basic ANSI colors, which every terminal has
+  Basic16,
+}
+impl ColorMode {
+  /// the value of `--colors=...` -> ColorMode
+  fn from_name(name: &str) -> Option<ColorMode> {
+    match name {
+      "truecolor" | "24bit" => return Some(ColorMode::TrueColor),
+      "256"                 => return Some(ColorMode::Xterm256),
+      "16"                  => return Some(ColorMode::Basic16),
+      _                     => return None,
+    }
   }
-  fn draw(&self) {
-    let pixel_char: ColoredString = ColoredString::from("██");
-    for column in 0..self.size_x {
-      for pixel in 0..self.size_y {
-        let color: CustomColor = self.pixels[column][pixel];
-        print!("{}", pixel_char.clone().custom_color(color));
+  /// palette indices with the color they (usually) stand for
+  fn palette(&self) -> Vec<(u8, CustomColor)> {
+    let mut palette: Vec<(u8, CustomColor)> = Vec::new();
+    match self {
+      ColorMode::TrueColor => {}
+      ColorMode::Xterm256 => {
+        // the first 16 are the basic colors, which terminals theme however they like, so they're left out
+        // 16-231: 6x6x6 color cube
+        let levels: [u8; 6] = [0, 95, 135, 175, 215, 255];
+        for (r_index, r) in levels.iter().enumerate() {
+          for (g_index, g) in levels.iter().enumerate() {
+            for (b_index, b) in levels.iter().enumerate() {
+              let index: usize = 16 + 36 * r_index + 6 * g_index + b_index;
+              palette.push((index as u8, CustomColor { r: *r, g: *g, b: *b }));
+            }
+          }
+        }
+        // 232-255: grayscale ramp
+        for i in 0..24 {
+          let level: u8 = 8 + 10 * i;
+          palette.push((232 + i, CustomColor { r: level, g: level, b: level }));
+        }
+      }
+      ColorMode::Basic16 => {
+        // xterm's default colors
+        let colors: [(u8, u8, u8); 16] = [
+          (0, 0, 0),       (205, 0, 0),   (0, 205, 0),   (205, 205, 0),
+          (0, 0, 238),     (205, 0, 205), (0, 205, 205), (229, 229, 229),
+          (127, 127, 127), (255, 0, 0),   (0, 255, 0),   (255, 255, 0),
+          (92, 92, 255),   (255, 0, 255), (0, 255, 255), (255, 255, 255),
+        ];
+        for (index, (r, g, b)) in colors.iter().enumerate() {
+          palette.push((index as u8, CustomColor { r: *r, g: *g, b: *b }));
+        }
       }
-      println!();
     }
+    return palette;
   }
 }
 
 /// enumarates all possible cubes
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 enum CubeType {
   Air,
   Grass,
   Stone,
   Wood,
+  Dirt,
+}
+const CUBE_TYPES: [CubeType; 5] = [CubeType::Air, CubeType::Grass, CubeType::Stone, CubeType::Wood, CubeType::Dirt];
+impl CubeType {
+  fn color(self) -> CustomColor {
+    return match self {
+      // air is never drawn, but see-through is close enough
+      CubeType::Air   => SKY_COLOR,
+      CubeType::Grass => GRASS_COLOR,
+      CubeType::Stone => STONE_COLOR,
+      CubeType::Wood  => WOOD_COLOR,
+      CubeType::Dirt  => DIRT_COLOR,
+    };
+  }
+  /// what it's called in save files
+  fn name(self) -> &'static str {
+    return match self {
+      CubeType::Air   => "air",
+      CubeType::Grass => "grass",
+      CubeType::Stone => "stone",
+      CubeType::Wood  => "wood",
+      CubeType::Dirt  => "dirt",
+    };
+  }
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
 
+    /// a world filled with `cube_type` from 0, 0, 0 up to (not including) `size`
+    fn filled_world(size: [i32; 3], cube_type: CubeType) -> World {
+      let mut world: World = World::new();
+      for x in 0..size[0] {
+        for y in 0..size[1] {
+          for z in 0..size[2] {
+            world.set([x, y, z], cube_type);
+          }
+        }
+      }
+      return world;
+    }
+
     #[test]
     fn dot_product() {
       let vec1: Vector3 = Vector3 { x: 0.0, y: 4.0, z: -4.0 };
@@ -619,88 +2306,805 @@ mod tests {
       assert_eq!(actual, expected);
     }
 
-    /// Test the 2D -> 1D projection function
+    /// Test the projection matrix (view space -> clip space -> NDC)
     #[test]
-    fn projection_2d_to_1d() {
-      // test data
-      let camera_position: Vector2 = Vector2 { x: 1.0, y: 1.0 };
-      let camera_rot: f32 = 45.0 * (PI/180.0);
-      let vertex: Vector2 = Vector2 { x: 12.0, y: 12.0 };
-
-      let (actual, _ )= project_2d_to_1d(vertex, camera_position, camera_rot);
-      let expected: f32 = 0.0; // in theory camera is looking directly at point
-
-      assert_eq!(actual, expected);
+    fn projection_to_ndc() {
+      let aspect_ratio: f32 = 2.0;
+      let projection: Matrix4 = projection_matrix(FIELD_OF_VIEW, aspect_ratio, NEAR_PLANE, FAR_PLANE);
+      let distance: f32 = 10.0;
+      // a point on the top edge of the field of view
+      let top_edge: f32 = f32::tan(FIELD_OF_VIEW / 2.0) * distance;
+      let vertex: Vector4 = Vector4 { x: top_edge * aspect_ratio, y: top_edge, z: distance, w: 1.0 };
+
+      let clip: Vector4 = projection.transform(vertex);
+      // should land in the top right corner, at the right depth
+      assert!((clip.x / clip.w - 1.0).abs() < 0.0001);
+      assert!((clip.y / clip.w - 1.0).abs() < 0.0001);
+      assert_eq!(clip.w, distance);
+
+      // the near and far planes map to 0 and 1
+      let near: Vector4 = projection.transform(Vector4 { x: 0.0, y: 0.0, z: NEAR_PLANE, w: 1.0 });
+      let far: Vector4 = projection.transform(Vector4 { x: 0.0, y: 0.0, z: FAR_PLANE, w: 1.0 });
+      assert!((near.z / near.w).abs() < 0.0001);
+      assert!((far.z / far.w - 1.0).abs() < 0.0001);
     }
 
     #[test]
     fn vertex_render() {
       // the camera should be pointing directly at the vertex
-      let vertex: Vector3 = Vector3 { x: 10.0, y: 10.0, z: 1.0 };
+      let vertex: Vector3 = Vector3 { x: 10.0, y: 1.0, z: 10.0 };
       let camera_position: Vector3 = Vector3 { x: 1.0, y: 1.0, z: 1.0 };
       let camera_rotation_horizontal: f32 = 45.0 * (PI/180.0);
       let camera_rotation_vertical: f32 = 0.0 * (PI/180.0);
+      let view_projection: Matrix4 = camera_matrix(camera_position, camera_rotation_vertical, camera_rotation_horizontal, SCREEN_Y_SIZE as f32 / SCREEN_X_SIZE as f32);
 
-      // so expected result is (0, 0)
-      let (actual, _) = render_vertex(vertex, camera_position, camera_rotation_vertical, camera_rotation_horizontal);
-      let expected: Vector2 = Vector2 { x: 0.0, y: 0.0 };
+      // so expected result is the middle of the screen
+      let (actual, depth) = clip_to_screen(to_clip_space(vertex, view_projection), SCREEN_X_SIZE, SCREEN_Y_SIZE);
+      let expected: Vector2 = Vector2 { x: SCREEN_X_SIZE as f32 / 2.0, y: SCREEN_Y_SIZE as f32 / 2.0 };
 
-      assert_eq!(actual, expected);
+      assert!((actual.x - expected.x).abs() < 0.001);
+      assert!((actual.y - expected.y).abs() < 0.001);
+      assert!((depth - vector3_distance(vertex, camera_position)).abs() < 0.001);
+
+      // looking up a bit, the vertex should now be below the middle of the screen
+      let view_projection: Matrix4 = camera_matrix(camera_position, 10.0 * (PI/180.0), camera_rotation_horizontal, SCREEN_Y_SIZE as f32 / SCREEN_X_SIZE as f32);
+      let (actual, _) = clip_to_screen(to_clip_space(vertex, view_projection), SCREEN_X_SIZE, SCREEN_Y_SIZE);
+      assert!(actual.x > expected.x);
+      assert!((actual.y - expected.y).abs() < 0.001);
     }
 
     #[test]
-    fn triangle_contains() {
+    fn triangle_rasterize() {
       let a: Vector2 = Vector2 { x: 0.0, y: 0.0 };
       let c: Vector2 = Vector2 { x: 0.0, y: 12.0 };
       let b: Vector2 = Vector2 { x: 12.0, y: 0.0 };
       let triangle: Triangle2D = Triangle2D { a, b, c, color: CustomColor { r: 0, g: 0, b: 0 } };
+      let fragments: Vec<Fragment> = triangle.rasterize(20, 20);
 
       let expected_1: bool = true;
-      let actual_1: bool = triangle.contains(Vector2 { x: 2.0, y: 2.0 });
+      let actual_1: bool = fragments.iter().any(|fragment| fragment.x == 2 && fragment.y == 2);
       assert_eq!(actual_1, expected_1);
 
       let expected_2: bool = false;
-      let actual_2: bool = triangle.contains(Vector2 { x: 12.0, y: 12.0 });
+      let actual_2: bool = fragments.iter().any(|fragment| fragment.x == 11 && fragment.y == 11);
       assert_eq!(actual_2, expected_2);
+
+      // nothing outside of the bounding box
+      assert!(fragments.iter().all(|fragment| fragment.x < 12 && fragment.y < 12));
+      // barycentric coordinates always add up to 1 and are never negative inside the triangle
+      for fragment in fragments {
+        let weights: Vector3 = fragment.weights;
+        assert!((weights.x + weights.y + weights.z - 1.0).abs() < 0.0001);
+        assert!(weights.x >= 0.0 && weights.y >= 0.0 && weights.z >= 0.0);
+      }
     }
+    /// two triangles sharing an edge should cover every pixel of their square exactly once
     #[test]
-    fn indexing() {
-      let position: Vector3 = Vector3 { z: 12.0, x: 12.0, y: 12.0 };
-      let index: usize = vector3_to_linear_index(position);
-      let calculated_position = linear_index_to_vector3(index);
-
-      assert_eq!(position, calculated_position);
+    fn rasterize_shared_edge() {
+      let a: Vector2 = Vector2 { x: 0.0, y: 0.0 };
+      let b: Vector2 = Vector2 { x: 0.0, y: 8.0 };
+      let c: Vector2 = Vector2 { x: 8.0, y: 8.0 };
+      let d: Vector2 = Vector2 { x: 8.0, y: 0.0 };
+      let black: CustomColor = CustomColor { r: 0, g: 0, b: 0 };
+      // the diagonal goes right through pixel centers
+      let triangle_1: Triangle2D = Triangle2D { a, b, c, color: black };
+      let triangle_2: Triangle2D = Triangle2D { a, b: c, c: d, color: black };
+
+      let mut coverage: Vec<Vec<u32>> = vec![vec![0; 8]; 8];
+      for fragment in triangle_1.rasterize(8, 8).iter().chain(triangle_2.rasterize(8, 8).iter()) {
+        coverage[fragment.x][fragment.y] += 1;
+      }
+      assert!(coverage.iter().flatten().all(|count| *count == 1));
+    }
+    /// the barycentric coordinates should follow the triangle's own vertex order, whatever its winding
+    #[test]
+    fn rasterize_weights_order() {
+      let a: Vector2 = Vector2 { x: 0.0, y: 0.0 };
+      let b: Vector2 = Vector2 { x: 0.0, y: 100.0 };
+      let c: Vector2 = Vector2 { x: 100.0, y: 0.0 };
+      let black: CustomColor = CustomColor { r: 0, g: 0, b: 0 };
+      let triangle: Triangle2D = Triangle2D { a, b, c, color: black };
+      let swapped_triangle: Triangle2D = Triangle2D { a, b: c, c: b, color: black };
+
+      // a pixel right next to b
+      let find_pixel = |fragments: Vec<Fragment>| -> Fragment {
+        return *fragments.iter().find(|fragment| fragment.x == 0 && fragment.y == 95).unwrap();
+      };
+      let weights: Vector3 = find_pixel(triangle.rasterize(100, 100)).weights;
+      assert!(weights.y > weights.x && weights.y > weights.z);
+      let weights: Vector3 = find_pixel(swapped_triangle.rasterize(100, 100)).weights;
+      assert!(weights.z > weights.x && weights.z > weights.y);
+    }
+    #[test]
+    fn chunked_world() {
+      let mut world: World = World::new();
+      assert_eq!(world.get([3, 5, 7]), CubeType::Air);
+      assert_eq!(world.bounds(), None);
+
+      world.set([3, 5, 7], CubeType::Stone);
+      // anywhere, not just near 0, 0, 0
+      world.set([-1, -20, 100], CubeType::Wood);
+      assert_eq!(world.get([3, 5, 7]), CubeType::Stone);
+      assert_eq!(world.get([-1, -20, 100]), CubeType::Wood);
+      assert_eq!(world.get([-1, -20, 99]), CubeType::Air);
+      assert_eq!(world.get([2, 5, 7]), CubeType::Air);
+      assert_eq!(world.chunks.len(), 2);
+      // setting air where there's nothing doesn't make chunks
+      world.set([1000, 0, 0], CubeType::Air);
+      assert_eq!(world.chunks.len(), 2);
+
+      // negative coordinates are in the chunk before 0
+      assert_eq!(World::chunk_coordinates([-1, 16, 0]), ([-1, 1, 0], 15 * 16 * 16));
+      assert_eq!(world.bounds(), Some(([-1, -20, 7], [4, 6, 101])));
+      let mut cubes: Vec<([i32; 3], CubeType)> = world.cubes().collect();
+      cubes.sort_by_key(|(position, _)| *position);
+      assert_eq!(cubes, vec![([-1, -20, 100], CubeType::Wood), ([3, 5, 7], CubeType::Stone)]);
+    }
+    #[test]
+    fn hidden_faces() {
+      let mut world: World = World::new();
+      // a lone cube has all of its 6 faces
+      world.set([4, 4, 4], CubeType::Stone);
+      assert_eq!(build_region_mesh(&world, [0; 3], [10; 3]).len(), 6 * 2);
+      // two cubes side by side hide one face each
+      world.set([5, 4, 4], CubeType::Grass);
+      assert_eq!(build_region_mesh(&world, [0; 3], [10; 3]).len(), 10 * 2);
+      // the hidden faces are the ones between the two cubes
+      assert!(build_region_mesh(&world, [0; 3], [10; 3]).iter().all(|triangle| triangle.a.x != 5.0 || triangle.b.x != 5.0 || triangle.c.x != 5.0));
+
+      // a completely solid world only shows its outer shell
+      let solid_world: World = filled_world([10; 3], CubeType::Stone);
+      let mut area: f32 = 0.0;
+      for triangle in build_region_mesh(&solid_world, [0; 3], [10; 3]) {
+        let ab: Vector3 = Vector3 { x: triangle.b.x - triangle.a.x, y: triangle.b.y - triangle.a.y, z: triangle.b.z - triangle.a.z };
+        let ac: Vector3 = Vector3 { x: triangle.c.x - triangle.a.x, y: triangle.c.y - triangle.a.y, z: triangle.c.z - triangle.a.z };
+        let cross_product: Vector3 = vector3_cross(ab, ac);
+        area += f32::sqrt(vector3_dot(cross_product, cross_product)) / 2.0;
+      }
+      assert_eq!(area, (6 * 10 * 10) as f32);
+    }
+    /// faces of the same type facing the same way should be merged
+    #[test]
+    fn greedy_meshing() {
+      // a completely solid world is just 6 big faces
+      let solid_world: World = filled_world([10; 3], CubeType::Stone);
+      assert_eq!(build_region_mesh(&solid_world, [0; 3], [10; 3]).len(), 6 * 2);
+
+      // two cubes of the same type side by side look like one long box
+      let mut world: World = World::new();
+      world.set([4, 4, 4], CubeType::Wood);
+      world.set([5, 4, 4], CubeType::Wood);
+      assert_eq!(build_region_mesh(&world, [0; 3], [10; 3]).len(), 6 * 2);
+
+      // an L shape can't be a single rectangle from above
+      world.set([4, 4, 5], CubeType::Wood);
+      let top_faces: usize = build_region_mesh(&world, [0; 3], [10; 3]).iter().filter(|triangle| triangle.normal.y == 1.0).count();
+      assert_eq!(top_faces, 2 * 2);
+    }
+    /// changing a cube should only rebuild the regions around it, and give the same result as a full rebuild
+    #[test]
+    fn world_mesh_cache() {
+      // all in the first chunk
+      let mut world: World = filled_world([10, 10, 10], CubeType::Stone);
+      let mut world_mesh: WorldMesh = WorldMesh::new(&world);
+      // nothing changed, nothing to rebuild
+      assert_eq!(world_mesh.update(&world), 0);
+
+      // right in the middle of a chunk
+      let position: [i32; 3] = [5, 5, 5];
+      world.set(position, CubeType::Air);
+      world_mesh.mark_dirty(position);
+      assert_eq!(world_mesh.update(&world), 1);
+      assert_eq!(world_mesh.chunks, WorldMesh::new(&world).chunks);
+
+      // on the corner of a chunk, touching 7 other chunks.
+      // the cube diagonally across the corner gets darker where it touches the new one
+      world.set([16, 14, 16], CubeType::Stone);
+      let mut world_mesh: WorldMesh = WorldMesh::new(&world);
+      let position: [i32; 3] = [15, 15, 15];
+      world.set(position, CubeType::Wood);
+      world_mesh.mark_dirty(position);
+      assert_eq!(world_mesh.update(&world), 8);
+      assert_eq!(world_mesh.chunks, WorldMesh::new(&world).chunks);
+    }
+    #[test]
+    fn hotbar() {
+      let mut screen: Screen = Screen { pixels: Vec::new(), depth: Vec::new(), size_x: 20, size_y: 30 };
+      screen.init();
+      let mut overlay: Overlay = Overlay::new(screen.size_x, screen.size_y);
+      draw_hotbar(&mut overlay, 1);
+      overlay.composite(&mut screen);
+      // 4 slots of 4 pixels with a gap of 1 between them, centered, with 1 pixel under them
+      let left: usize = (30 - 19) / 2;
+      assert_eq!(screen.pixels[15][left], HOTBAR_BORDER_COLOR);
+      assert_eq!(screen.pixels[16][left + 1], GRASS_COLOR);
+      assert_eq!(screen.pixels[15][left + 5], HOTBAR_SELECTED_COLOR);
+      assert_eq!(screen.pixels[17][left + 7], DIRT_COLOR);
+      assert_eq!(screen.pixels[16][left + 12], STONE_COLOR);
+      assert_eq!(screen.pixels[16][left + 17], WOOD_COLOR);
+      // the gaps and what's around the hotbar are left alone
+      assert_eq!(screen.pixels[16][left + 4], SKY_COLOR);
+      assert_eq!(screen.pixels[19][left + 1], SKY_COLOR);
+      assert_eq!(screen.pixels[14][left + 1], SKY_COLOR);
+
+      // and it doesn't go off the edge of screens too small for it
+      draw_hotbar(&mut Overlay::new(2, 5), 0);
+    }
+    #[test]
+    fn overlay_text_and_panels() {
+      let mut screen: Screen = Screen { pixels: Vec::new(), depth: Vec::new(), size_x: 10, size_y: 12 };
+      screen.init();
+      let mut overlay: Overlay = Overlay::new(screen.size_x, screen.size_y);
+      overlay.draw_label(0, 0, &["1".to_string()]);
+      overlay.draw_crosshair();
+      overlay.composite(&mut screen);
+
+      // the 1 with a pixel of panel around it
+      let dark_sky: CustomColor = mix_colors(SKY_COLOR, PANEL_COLOR, PANEL_OPACITY);
+      assert_eq!(screen.pixels[0][0], dark_sky);
+      assert_eq!(screen.pixels[1][1], dark_sky);
+      assert_eq!(screen.pixels[1][2], TEXT_COLOR);
+      assert_eq!(screen.pixels[2][1], TEXT_COLOR);
+      assert_eq!(screen.pixels[5][3], TEXT_COLOR);
+      assert_eq!(screen.pixels[6][4], dark_sky);
+      // the panel ends there
+      assert_eq!(screen.pixels[7][0], SKY_COLOR);
+      assert_eq!(screen.pixels[0][5], SKY_COLOR);
+
+      // a plus in the middle
+      assert_eq!(screen.pixels[5][6], CROSSHAIR_COLOR);
+      assert_eq!(screen.pixels[4][6], CROSSHAIR_COLOR);
+      assert_eq!(screen.pixels[5][7], CROSSHAIR_COLOR);
+      assert_eq!(screen.pixels[4][7], SKY_COLOR);
+    }
+    /// only what changed since the last frame should be sent to the terminal
+    #[test]
+    fn differential_output() {
+      let mut screen: Screen = Screen { pixels: Vec::new(), depth: Vec::new(), size_x: 4, size_y: 4 };
+      screen.init();
+      let mut terminal_output: TerminalOutput = TerminalOutput::new(PixelMode::FullBlock, ColorMode::TrueColor);
+
+      // first frame: everything, starting from a cleared terminal, with the color only set once
+      let frame: String = terminal_output.render_frame(&screen);
+      assert!(frame.starts_with("\x1b[2J"));
+      assert_eq!(frame.matches("██").count(), 16);
+      assert_eq!(frame.matches(&format!("\x1b[38;2;{};{};{}m", SKY_COLOR.r, SKY_COLOR.g, SKY_COLOR.b)).count(), 1);
+
+      // nothing changed: nothing drawn
+      let frame: String = terminal_output.render_frame(&screen);
+      assert_eq!(frame.matches("██").count(), 0);
+
+      // 2 pixels next to each other changed: one cursor move, one color
+      let red: CustomColor = CustomColor { r: 255, g: 0, b: 0 };
+      screen.pixels[2][1] = red;
+      screen.pixels[2][2] = red;
+      let frame: String = terminal_output.render_frame(&screen);
+      assert_eq!(frame.matches("██").count(), 2);
+      assert_eq!(frame.matches("\x1b[38;2;255;0;0m").count(), 1);
+      // row 3, column 3 in terminal coordinates (pixels are 2 characters wide)
+      assert!(frame.starts_with("\x1b[3;3H"));
+    }
+    /// colors should map to the palette entry that looks the closest
+    #[test]
+    fn palette_colors() {
+      let mut xterm_256: TerminalOutput = TerminalOutput::new(PixelMode::FullBlock, ColorMode::Xterm256);
+      // exact matches
+      assert_eq!(xterm_256.color_escape(CustomColor { r: 255, g: 0, b: 0 }, true), "\x1b[38;5;196m");
+      assert_eq!(xterm_256.color_escape(CustomColor { r: 0, g: 0, b: 0 }, false), "\x1b[48;5;16m");
+      // a gray is closer to the grayscale ramp than to the color cube
+      assert_eq!(xterm_256.color_escape(STONE_COLOR, true), "\x1b[38;5;244m");
+
+      let mut basic_16: TerminalOutput = TerminalOutput::new(PixelMode::FullBlock, ColorMode::Basic16);
+      assert_eq!(basic_16.color_escape(GRASS_COLOR, true), "\x1b[92m");
+      assert_eq!(basic_16.color_escape(CustomColor { r: 20, g: 10, b: 10 }, false), "\x1b[40m");
+      assert_eq!(basic_16.color_escape(STONE_COLOR, true), "\x1b[90m");
+      // a dark green is a (non bright) green, not a gray
+      assert_eq!(basic_16.color_escape(CustomColor { r: 20, g: 160, b: 20 }, true), "\x1b[32m");
+    }
+    #[test]
+    fn half_block_cells() {
+      let mut screen: Screen = Screen { pixels: Vec::new(), depth: Vec::new(), size_x: 3, size_y: 2 };
+      screen.init();
+      let red: CustomColor = CustomColor { r: 255, g: 0, b: 0 };
+      let blue: CustomColor = CustomColor { r: 0, g: 0, b: 255 };
+      screen.pixels[0][1] = red;
+      screen.pixels[1][1] = blue;
+
+      let cells: Vec<Vec<Cell>> = screen.cells(PixelMode::HalfBlock);
+      // 3 rows of pixels fit in 2 rows of characters
+      assert_eq!(cells.len(), 2);
+      assert_eq!(cells[0][1], Cell { foreground: red, background: blue });
+      assert_eq!(cells[1][0].background, CustomColor { r: 0, g: 0, b: 0 });
+    }
+    #[test]
+    fn quit_keys() {
+      assert!(is_quit_key(&Key::Escape));
+      assert!(is_quit_key(&Key::CtrlC));
+      assert!(!is_quit_key(&Key::Char('q')));
+    }
+    #[test]
+    fn terminal_screen_size() {
+      // full blocks: 2 characters per pixel
+      assert_eq!(screen_size(24, 80, PixelMode::FullBlock), (23, 40));
+      // half blocks: 2 pixels per character
+      assert_eq!(screen_size(24, 80, PixelMode::HalfBlock), (23 * 2, 80));
+      // tiny terminals still get a screen
+      assert_eq!(screen_size(1, 1, PixelMode::FullBlock), (1, 1));
+    }
+    /// the top, sides and bottom of a cube should all look different
+    #[test]
+    fn directional_shading() {
+      let white: CustomColor = CustomColor { r: 255, g: 255, b: 255 };
+      let top: CustomColor = shade(white, Vector3 { x: 0.0, y: 1.0, z: 0.0 });
+      let front: CustomColor = shade(white, Vector3 { x: 0.0, y: 0.0, z: -1.0 });
+      let left: CustomColor = shade(white, Vector3 { x: -1.0, y: 0.0, z: 0.0 });
+      let bottom: CustomColor = shade(white, Vector3 { x: 0.0, y: -1.0, z: 0.0 });
+      assert!(top.r > front.r);
+      assert!(front.r > left.r);
+      assert!(left.r > bottom.r);
+      // faces in the shade still get the ambient light
+      let ambient: u8 = (255.0 * AMBIENT_LIGHT).round() as u8;
+      assert_eq!(bottom, CustomColor { r: ambient, g: ambient, b: ambient });
+      // and the hue doesn't change
+      assert_eq!(shade(GRASS_COLOR, Vector3 { x: 0.0, y: 1.0, z: 0.0 }).r, 0);
+    }
+    #[test]
+    fn ambient_occlusion() {
+      assert_eq!(vertex_ambient_occlusion(false, false, false), 3);
+      assert_eq!(vertex_ambient_occlusion(false, false, true), 2);
+      assert_eq!(vertex_ambient_occlusion(true, false, true), 1);
+      // boxed in by the sides, the corner doesn't matter
+      assert_eq!(vertex_ambient_occlusion(true, true, false), 0);
+
+      // two cubes on the floor, with a third one on top of the second one
+      let mut world: World = World::new();
+      world.set([4, 4, 4], CubeType::Stone);
+      world.set([5, 4, 4], CubeType::Stone);
+      world.set([5, 5, 4], CubeType::Stone);
+      let triangles: Vec<Triangle3D> = build_region_mesh(&world, [0; 3], [10; 3]);
+
+      // the top of the first cube is darker where it touches the wall made by the third one
+      let top_of_first_cube: Vec<&Triangle3D> = triangles.iter().filter(|triangle| triangle.normal.y == 1.0 && triangle.a.y == 5.0).collect();
+      assert_eq!(top_of_first_cube.len(), 2);
+      for triangle in top_of_first_cube {
+        for (vertex, light) in [triangle.a, triangle.b, triangle.c].iter().zip(triangle.ambient_occlusion) {
+          if vertex.x == 5.0 {
+            assert_eq!(light, AMBIENT_OCCLUSION_LIGHT[2]);
+          } else {
+            assert_eq!(light, 1.0);
+          }
+        }
+      }
+      // nothing around the top of the third cube
+      let top_of_third_cube: Vec<&Triangle3D> = triangles.iter().filter(|triangle| triangle.normal.y == 1.0 && triangle.a.y == 6.0).collect();
+      assert!(top_of_third_cube.iter().all(|triangle| triangle.ambient_occlusion == [1.0; 3]));
+    }
+    #[test]
+    fn fog() {
+      let linear: Fog = Fog::Linear { start: 10.0, end: 20.0 };
+      assert_eq!(linear.amount(5.0), 0.0);
+      assert_eq!(linear.amount(15.0), 0.5);
+      assert_eq!(linear.amount(100.0), 1.0);
+
+      let exponential: Fog = Fog::Exponential { density: 0.1 };
+      assert_eq!(exponential.amount(0.0), 0.0);
+      assert!(exponential.amount(10.0) < exponential.amount(20.0));
+      assert!(exponential.amount(1000.0) > 0.999);
+
+      // picked with `--fog=`
+      assert_eq!(Fog::from_name("linear"), Some(LINEAR_FOG));
+      assert_eq!(Fog::from_name("linear:10:20"), Some(linear));
+      assert_eq!(Fog::from_name("exponential:0.1"), Some(exponential));
+      assert_eq!(Fog::from_name("exponential"), Some(EXPONENTIAL_FOG));
+      assert_eq!(Fog::from_name("linear:20:10"), None);
+      assert_eq!(Fog::from_name("exponential:-1"), None);
+      assert_eq!(Fog::from_name("fuzzy"), None);
+
+      // far away things end up the color of the sky
+      let black: CustomColor = CustomColor { r: 0, g: 0, b: 0 };
+      assert_eq!(mix_colors(black, SKY_COLOR, linear.amount(100.0)), SKY_COLOR);
+      assert_eq!(mix_colors(black, SKY_COLOR, linear.amount(0.0)), black);
     }
-
     #[test]
     fn triangle_depth() {
-      let camera_position: Vector3 = Vector3 { x: 1.0, y: 1.0, z: 1.0 };
+      let camera_position: Vector3 = Vector3 { x: 1.0, y: 1.0, z: -10.0 };
       let camera_rotation_horizontal: f32 = 0.0 * (PI/180.0);
       let camera_rotation_vertical: f32 = 0.0 * (PI/180.0);
       let c: Vector3 = Vector3 { x: 0.0, y: 12.0, z: 0.0 };
       let a: Vector3 = Vector3 { x: 0.0, y: 0.0, z: 0.0 };
       let b: Vector3 = Vector3 { x: 12.0, y: 0.0, z: 0.0 };
       let n: Vector3 = Vector3 { x: 0.0, y: 0.0, z: 0.0 };
-      let triangle1: Triangle3D = Triangle3D { a, b, c, color: CustomColor { r: 0, g: 0, b: 0 }, normal: n };
+      let triangle1: Triangle3D = Triangle3D { a, b, c, color: CustomColor { r: 0, g: 0, b: 0 }, normal: n, ambient_occlusion: [1.0; 3] };
 
       let a2: Vector3 = Vector3 { x: 10.0, y: 10.0, z: 10.0 };
       let c2: Vector3 = Vector3 { x: 10.0, y: 12.0, z: 10.0 };
       let b2: Vector3 = Vector3 { x: 12.0, y: 10.0, z: 10.0 };
       let n2: Vector3 = Vector3 { x: 10.0, y: 10.0, z: 10.0 };
-      let triangle2: Triangle3D = Triangle3D { a: a2, b: b2, c: c2, color: CustomColor { r: 0, g: 0, b: 0 }, normal: n2 };
+      let triangle2: Triangle3D = Triangle3D { a: a2, b: b2, c: c2, color: CustomColor { r: 0, g: 0, b: 0 }, normal: n2, ambient_occlusion: [1.0; 3] };
 
-      let (_, depth_1) = render_triangle(triangle1, camera_position, camera_rotation_vertical, camera_rotation_horizontal);
-      let (_, depth_2) = render_triangle(triangle2, camera_position, camera_rotation_vertical, camera_rotation_horizontal);
+      let view_projection: Matrix4 = camera_matrix(camera_position, camera_rotation_vertical, camera_rotation_horizontal, SCREEN_Y_SIZE as f32 / SCREEN_X_SIZE as f32);
+      let depth_1: f32 = render_triangle(triangle1, view_projection, SCREEN_X_SIZE, SCREEN_Y_SIZE)[0].depth_a;
+      let depth_2: f32 = render_triangle(triangle2, view_projection, SCREEN_X_SIZE, SCREEN_Y_SIZE)[0].depth_a;
 
       assert!(depth_1 < depth_2);
     }
     #[test]
+    fn triangle_clipping() {
+      let camera_position: Vector3 = Vector3 { x: 0.0, y: 0.0, z: 0.0 };
+      let view_projection: Matrix4 = camera_matrix(camera_position, 0.0, 0.0, SCREEN_Y_SIZE as f32 / SCREEN_X_SIZE as f32);
+      let black: CustomColor = CustomColor { r: 0, g: 0, b: 0 };
+      let n: Vector3 = Vector3 { x: 0.0, y: 0.0, z: 0.0 };
+
+      // entirely behind the camera
+      let behind: Triangle3D = Triangle3D {
+        a: Vector3 { x: -1.0, y: 0.0, z: -5.0 },
+        b: Vector3 { x: 1.0, y: 0.0, z: -5.0 },
+        c: Vector3 { x: 0.0, y: 1.0, z: -5.0 },
+        color: black, normal: n, ambient_occlusion: [1.0; 3],
+      };
+      assert_eq!(render_triangle(behind, view_projection, SCREEN_X_SIZE, SCREEN_Y_SIZE).len(), 0);
+
+      // in front of the camera, but way off to the side
+      let aside: Triangle3D = Triangle3D {
+        a: Vector3 { x: 100.0, y: 0.0, z: 5.0 },
+        b: Vector3 { x: 101.0, y: 0.0, z: 5.0 },
+        c: Vector3 { x: 100.0, y: 1.0, z: 5.0 },
+        color: black, normal: n, ambient_occlusion: [1.0; 3],
+      };
+      assert_eq!(render_triangle(aside, view_projection, SCREEN_X_SIZE, SCREEN_Y_SIZE).len(), 0);
+
+      // one vertex behind the camera: the rest is a quad, so two triangles
+      let one_behind: Triangle3D = Triangle3D {
+        a: Vector3 { x: 0.0, y: -1.0, z: -5.0 },
+        b: Vector3 { x: 1.0, y: -1.0, z: 5.0 },
+        c: Vector3 { x: -1.0, y: -1.0, z: 5.0 },
+        color: black, normal: n, ambient_occlusion: [1.0; 3],
+      };
+      let rendered: Vec<RenderedTriangle2D> = render_triangle(one_behind, view_projection, SCREEN_X_SIZE, SCREEN_Y_SIZE);
+      assert_eq!(rendered.len(), 2);
+      for triangle in rendered {
+        for depth in [triangle.depth_a, triangle.depth_b, triangle.depth_c] {
+          assert!(depth >= NEAR_PLANE - 0.0001);
+        }
+      }
+
+      // two vertices behind the camera: only a smaller triangle is left
+      let two_behind: Triangle3D = Triangle3D {
+        a: Vector3 { x: 0.0, y: -1.0, z: 5.0 },
+        b: Vector3 { x: 1.0, y: -1.0, z: -5.0 },
+        c: Vector3 { x: -1.0, y: -1.0, z: -5.0 },
+        color: black, normal: n, ambient_occlusion: [1.0; 3],
+      };
+      let rendered: Vec<RenderedTriangle2D> = render_triangle(two_behind, view_projection, SCREEN_X_SIZE, SCREEN_Y_SIZE);
+      assert_eq!(rendered.len(), 1);
+      assert!(rendered[0].depth_b >= NEAR_PLANE - 0.0001);
+      assert!(rendered[0].depth_c >= NEAR_PLANE - 0.0001);
+    }
+    /// closer pixels should win no matter the order they are drawn in
+    #[test]
+    fn depth_buffer() {
+      let near_color: CustomColor = CustomColor { r: 255, g: 0, b: 0 };
+      let far_color: CustomColor = CustomColor { r: 0, g: 0, b: 255 };
+      let mut screen: Screen = Screen { pixels: Vec::new(), depth: Vec::new(), size_x: 2, size_y: 2 };
+      screen.init();
+
+      screen.plot(0, 0, far_color, 10.0);
+      screen.plot(0, 0, near_color, 5.0);
+      screen.plot(0, 0, far_color, 10.0);
+      assert_eq!(screen.pixels[0][0], near_color);
+      assert_eq!(screen.depth[0][0], 5.0);
+      // other pixels are untouched
+      assert_eq!(screen.depth[1][1], f32::INFINITY);
+    }
+    /// depth should be interpolated perspective-correctly across a triangle
+    #[test]
+    fn interpolated_depth() {
+      let triangle: Triangle2D = Triangle2D {
+        a: Vector2 { x: 0.0, y: 0.0 },
+        b: Vector2 { x: 10.0, y: 0.0 },
+        c: Vector2 { x: 0.0, y: 10.0 },
+        color: CustomColor { r: 0, g: 0, b: 0 },
+      };
+      let rendered_triangle: RenderedTriangle2D = RenderedTriangle2D { triangle, depth_a: 2.0, depth_b: 4.0, depth_c: 4.0, ambient_occlusion: [1.0, 0.0, 0.0] };
+
+      // vertices keep their own depth
+      assert!((rendered_triangle.depth_at(Vector3 { x: 1.0, y: 0.0, z: 0.0 }) - 2.0).abs() < 0.0001);
+      assert!((rendered_triangle.depth_at(Vector3 { x: 0.0, y: 1.0, z: 0.0 }) - 4.0).abs() < 0.0001);
+      // halfway between a and b on screen is closer to a in depth (1 / ((1/2 + 1/4) / 2))
+      let halfway: Vector3 = Vector3 { x: 0.5, y: 0.5, z: 0.0 };
+      assert!((rendered_triangle.depth_at(halfway) - 8.0 / 3.0).abs() < 0.0001);
+      // other values are interpolated the same way: closer to a's than an even split
+      assert!((rendered_triangle.ambient_occlusion_at(halfway) - 2.0 / 3.0).abs() < 0.0001);
+    }
+    #[test]
+    fn noise() {
+      // the same every time, but not for every seed
+      assert_eq!(gradient_noise(7, 3.3, -1.6), gradient_noise(7, 3.3, -1.6));
+      assert_ne!(gradient_noise(7, 3.3, -1.6), gradient_noise(8, 3.3, -1.6));
+      // flat on the grid
+      assert_eq!(gradient_noise(7, 3.0, -2.0), 0.0);
+      let mut previous: f32 = gradient_noise(7, 0.0, 0.5);
+      for step in 1..1000 {
+        let noise: f32 = gradient_noise(7, step as f32 * 0.01, 0.5);
+        assert!(noise.abs() < 1.0);
+        // no jumps
+        assert!((noise - previous).abs() < 0.05);
+        previous = noise;
+      }
+    }
+    #[test]
+    fn generated_terrain() {
+      let mut world: World = World::new();
+      generate_chunk(&mut world, 42, [0, 0, 0]);
+      generate_chunk(&mut world, 42, [0, 1, 0]);
+      // nothing below the ground
+      generate_chunk(&mut world, 42, [0, -1, 0]);
+      assert_eq!(world.get([3, -1, 3]), CubeType::Air);
+      for (x, z) in [(0, 0), (3, 11), (15, 15)] {
+        let height: i32 = terrain_height(42, x, z);
+        assert!(height > 0 && height < 2 * CHUNK_SIZE);
+        assert_eq!(world.get([x, height, z]), CubeType::Air);
+        assert_eq!(world.get([x, height - 1, z]), CubeType::Grass);
+        assert_eq!(world.get([x, height - 2, z]), if height >= 2 { CubeType::Dirt } else { CubeType::Air });
+        if height > DIRT_DEPTH + 1 {
+          assert_eq!(world.get([x, 0, z]), CubeType::Stone);
+        }
+      }
+      // the player starts on the ground
+      assert_eq!(spawn_position(&world).y, terrain_height(42, 0, 0) as f32 + EYE_HEIGHT);
+    }
+    #[test]
     fn angle_to_vector3() {
       let horizontal: f32 = 45.0 * (PI/180.0);
       let vertical: f32 = 45.0 * (PI/180.0);
       let actual_vector: Vector3 = angle_couple_to_vector3(horizontal, vertical);
-      let expected_vector = Vector3{x: 0.71, y:0.71, z:0.71};
-      assert_eq!(actual_vector, expected_vector);
+      let expected_vector = Vector3{x: 0.5, y: 0.70710677, z: 0.5};
+      assert!(vector3_distance(actual_vector, expected_vector) < 0.0001);
+      // always normalized
+      assert!((vector3_dot(actual_vector, actual_vector) - 1.0).abs() < 0.0001);
+    }
+    /// faces pointing sideways can be seen from the side, even if the camera isn't looking against them
+    #[test]
+    fn backface_culling_uses_camera_position() {
+      // a face on the x = 0 plane, pointing towards +x
+      let tris: Triangle3D = Triangle3D {
+        a: Vector3 { x: 0.0, y: 0.0, z: 5.0 },
+        b: Vector3 { x: 0.0, y: 1.0, z: 5.0 },
+        c: Vector3 { x: 0.0, y: 0.0, z: 6.0 },
+        color: CustomColor { r: 0, g: 0, b: 0 },
+        normal: Vector3 { x: 1.0, y: 0.0, z: 0.0 },
+        ambient_occlusion: [1.0; 3],
+      };
+      let forward: Vector3 = angle_couple_to_vector3(0.0, 0.0);
+      // camera looking down +z, off to the right of the face: visible
+      let camera_position: Vector3 = Vector3 { x: 2.0, y: 0.0, z: 0.0 };
+      assert!(faces_camera(&tris, camera_position));
+      assert!(!is_behind_camera(&tris, camera_position, forward));
+      // from the left it's the back of the face
+      assert!(!faces_camera(&tris, Vector3 { x: -2.0, y: 0.0, z: 0.0 }));
+      // and from past it, it's behind the camera
+      assert!(is_behind_camera(&tris, Vector3 { x: 2.0, y: 0.0, z: 10.0 }, forward));
+    }
+    #[test]
+    fn movement_follows_yaw() {
+      // looking down +x, forward is +x and right is -z
+      let yaw: f32 = 90.0 * (PI/180.0);
+      let forward: Vector3 = movement_vector(yaw, 1.0, 0.0);
+      assert!(vector3_distance(forward, Vector3 { x: 1.0, y: 0.0, z: 0.0 }) < 0.0001);
+      let right: Vector3 = movement_vector(yaw, 0.0, 1.0);
+      assert!(vector3_distance(right, Vector3 { x: 0.0, y: 0.0, z: -1.0 }) < 0.0001);
+      // with no yaw it's the old w = +z, d = +x
+      let diagonal: Vector3 = movement_vector(0.0, 1.0, 1.0);
+      assert!(vector3_distance(diagonal, Vector3 { x: 1.0, y: 0.0, z: 1.0 }) < 0.0001);
+    }
+    #[test]
+    fn player_keys() {
+      let mut player: Player = Player::new(Vector3 { x: 0.0, y: 0.0, z: 0.0 });
+      player.rotation_horizontal = 90.0 * (PI/180.0);
+      // looking down +x
+      player.handle_key(&Key::Char('w'));
+      assert!(vector3_distance(player.position, Vector3 { x: MOVEMENT_STEP, y: 0.0, z: 0.0 }) < 0.0001);
+      // can't look further up than straight up
+      for _ in 0..100 {
+        player.handle_key(&Key::ArrowUp);
+      }
+      assert_eq!(player.rotation_vertical, MAX_PITCH);
+      // turning around all the way ends up where it started
+      for _ in 0..72 {
+        player.handle_key(&Key::ArrowRight);
+      }
+      assert!((player.rotation_horizontal - 90.0 * (PI/180.0)).abs() < 0.001);
+    }
+    #[test]
+    fn player_falls_and_jumps() {
+      // a stone floor
+      let mut world: World = World::new();
+      for x in 0..10 {
+        for z in 0..10 {
+          world.set([x, 0, z], CubeType::Stone);
+        }
+      }
+      let mut player: Player = Player::new(Vector3 { x: 5.0, y: 8.0, z: 5.0 });
+      player.handle_key(&Key::Char('g'));
+      assert_eq!(player.movement_mode, MovementMode::Walk);
+      for _ in 0..40 {
+        player.tick(&world);
+      }
+      // standing on the floor
+      assert!(player.on_ground);
+      assert!((player.position.y - (1.0 + EYE_HEIGHT)).abs() < 0.0001);
+
+      player.handle_key(&Key::Char(' '));
+      let mut highest: f32 = player.position.y;
+      for _ in 0..40 {
+        player.tick(&world);
+        highest = highest.max(player.position.y);
+      }
+      // up a little over a cube, and back down
+      assert!(highest - (1.0 + EYE_HEIGHT) > 1.0);
+      assert!(player.on_ground);
+      assert!((player.position.y - (1.0 + EYE_HEIGHT)).abs() < 0.0001);
+    }
+    #[test]
+    fn player_stops_at_walls() {
+      // a floor, with a wall along x = 7 on top of it
+      let mut world: World = World::new();
+      for x in 0..10 {
+        for z in 0..10 {
+          world.set([x, 0, z], CubeType::Stone);
+        }
+      }
+      for y in 1..3 {
+        for z in 0..10 {
+          world.set([7, y, z], CubeType::Stone);
+        }
+      }
+      let mut player: Player = Player::new(Vector3 { x: 2.0, y: 1.0 + EYE_HEIGHT, z: 5.0 });
+      player.movement_mode = MovementMode::Walk;
+      // walk into the wall, slightly diagonally
+      player.rotation_horizontal = 80.0 * (PI/180.0);
+      for _ in 0..60 {
+        player.handle_key(&Key::Char('w'));
+        player.tick(&world);
+      }
+      // touching the wall, not in it
+      assert!((player.bounding_box().max[0] - 7.0).abs() < 0.0001);
+      // but still sliding along it
+      assert!(player.position.z > 5.5);
+    }
+    #[test]
+    fn raycast_hits_first_cube() {
+      let mut world: World = World::new();
+      world.set([5, 2, 6], CubeType::Wood);
+      world.set([5, 2, 8], CubeType::Stone);
+
+      // straight down +z, hits the wood and not the stone behind it
+      let hit: RaycastHit = raycast(&world, Vector3 { x: 5.5, y: 2.5, z: 1.5 }, Vector3 { x: 0.0, y: 0.0, z: 1.0 }, 10.0).unwrap();
+      assert_eq!(hit.cube_type, CubeType::Wood);
+      assert_eq!(hit.position, [5, 2, 6]);
+      assert_eq!(hit.normal, [0, 0, -1]);
+      assert!((hit.distance - 4.5).abs() < 0.0001);
+
+      // diagonally down onto the top of it
+      let hit: RaycastHit = raycast(&world, Vector3 { x: 5.5, y: 5.0, z: 4.5 }, Vector3 { x: 0.0, y: -1.0, z: 1.0 }, 10.0).unwrap();
+      assert_eq!(hit.position, [5, 2, 6]);
+      assert_eq!(hit.normal, [0, 1, 0]);
+      assert!((hit.distance - 2.0 * f32::sqrt(2.0)).abs() < 0.0001);
+
+      // too far away, or looking the other way
+      assert!(raycast(&world, Vector3 { x: 5.5, y: 2.5, z: 1.5 }, Vector3 { x: 0.0, y: 0.0, z: 1.0 }, 4.0).is_none());
+      assert!(raycast(&world, Vector3 { x: 5.5, y: 2.5, z: 1.5 }, Vector3 { x: 0.0, y: 0.0, z: -1.0 }, 10.0).is_none());
+    }
+    #[test]
+    fn break_and_place() {
+      let mut world: World = World::new();
+      let wall: [i32; 3] = [5, 2, 6];
+      world.set(wall, CubeType::Stone);
+      // looking straight at the wall from 3 cubes away
+      let mut player: Player = Player::new(Vector3 { x: 5.5, y: 2.5, z: 3.5 });
+      player.handle_key(&Key::Char('4'));
+      assert_eq!(player.selected_cube(), CubeType::Wood);
+
+      let placed: [i32; 3] = [5, 2, 5];
+      assert_eq!(player.place_on_target(&mut world), Some(placed));
+      assert_eq!(world.get(placed), CubeType::Wood);
+      let placed_closer: [i32; 3] = [5, 2, 4];
+      assert_eq!(player.place_on_target(&mut world), Some(placed_closer));
+      // too close now, the next one would go where the player is
+      assert_eq!(player.place_on_target(&mut world), None);
+
+      assert_eq!(player.break_target(&mut world), Some(placed_closer));
+      assert_eq!(player.break_target(&mut world), Some(placed));
+      assert_eq!(player.break_target(&mut world), Some(wall));
+      assert_eq!(world.get(wall), CubeType::Air);
+      // nothing left to break
+      assert_eq!(player.break_target(&mut world), None);
+    }
+    #[test]
+    fn broken_binary_world() {
+      // everything after WORLD_FILE_MAGIC for a world of `size`, with only air in the palette
+      let header = |version: u8, size: [i32; 3]| -> Vec<u8> {
+        let mut contents: Vec<u8> = vec![version];
+        for number in [0; 3].into_iter().chain(size) {
+          contents.extend(number.to_le_bytes());
+        }
+        contents.extend([1, 3]);
+        contents.extend(b"air");
+        return contents;
+      };
+      let fails = |contents: Vec<u8>| -> bool {
+        return std::panic::catch_unwind(|| read_binary_world(&contents)).is_err();
+      };
+      // 2 runs of 4 air
+      let mut contents: Vec<u8> = header(WORLD_FILE_VERSION, [2, 2, 2]);
+      contents.extend([WORLD_FILE_RUNS, 4, 0, 4, 0]);
+      assert_eq!(read_binary_world(&contents).2, vec![CubeType::Air; 8]);
+      // there never was a version 0, and newer ones can't be read
+      contents[0] = 0;
+      assert!(fails(contents.clone()));
+      contents[0] = WORLD_FILE_VERSION + 1;
+      assert!(fails(contents));
+      // a huge size with nothing behind it shouldn't try to make room for it all
+      assert!(fails([header(WORLD_FILE_VERSION, [1000; 3]), vec![WORLD_FILE_PACKED]].concat()));
+      // or too big to even count
+      assert!(fails([header(WORLD_FILE_VERSION, [i32::MAX; 3]), vec![WORLD_FILE_RUNS, 1, 0]].concat()));
+      // a run length that never ends
+      assert!(fails([header(WORLD_FILE_VERSION, [2, 2, 2]), vec![WORLD_FILE_RUNS], vec![0xff; 20]].concat()));
+      // more cubes than fit
+      assert!(fails([header(WORLD_FILE_VERSION, [2, 2, 2]), vec![WORLD_FILE_RUNS, 9, 0]].concat()));
+    }
+    #[test]
+    fn save_and_load_world() {
+      // the old format, without a header: 10³ digits
+      let legacy_path: std::path::PathBuf = std::env::temp_dir().join("termcraft_load_legacy_world.rmc");
+      let legacy_path: &str = legacy_path.to_str().unwrap();
+      let legacy_contents: String = (0..1000).map(|index| ["1", "2", "3"][index % 3]).collect();
+      std::fs::write(legacy_path, legacy_contents).unwrap();
+      let mut world: World = load_world(legacy_path);
+      std::fs::remove_file(legacy_path).unwrap();
+      assert_eq!(world.bounds(), Some(([0; 3], [10; 3])));
+      assert_eq!(world.get([0, 0, 1]), CubeType::Stone);
+      assert_eq!(world.get([0, 0, 2]), CubeType::Wood);
+      // and something the old format couldn't save
+      world.set([-3, 20, 4], CubeType::Wood);
+
+      let path: std::path::PathBuf = std::env::temp_dir().join("termcraft_save_and_load_world.rmc");
+      let path: &str = path.to_str().unwrap();
+      save_world(path, &world).unwrap();
+      // the temporary file was moved into place
+      assert!(!std::path::Path::new(&format!("{path}.tmp")).exists());
+      // compressed: a lot smaller than a digit per cube
+      let contents: Vec<u8> = std::fs::read(path).unwrap();
+      assert!(contents.starts_with(WORLD_FILE_MAGIC));
+      assert_eq!(contents[WORLD_FILE_MAGIC.len()], WORLD_FILE_VERSION);
+      assert!(contents.len() < 13 * 21 * 10 / 3);
+      let loaded_world: World = load_world(path);
+      std::fs::remove_file(path).unwrap();
+
+      let mut cubes: Vec<([i32; 3], CubeType)> = world.cubes().collect();
+      cubes.sort_by_key(|(position, _)| *position);
+      let mut loaded_cubes: Vec<([i32; 3], CubeType)> = loaded_world.cubes().collect();
+      loaded_cubes.sort_by_key(|(position, _)| *position);
+      assert_eq!(loaded_cubes, cubes);
+
+      // big areas of the same cube are just a few runs
+      let mut world: World = filled_world([20, 20, 20], CubeType::Stone);
+      world.set([7, 19, 3], CubeType::Air);
+      save_world(path, &world).unwrap();
+      assert!(std::fs::read(path).unwrap().len() < 60);
+      let loaded_world: World = load_world(path);
+      std::fs::remove_file(path).unwrap();
+      assert_eq!(loaded_world.cubes().count(), 20 * 20 * 20 - 1);
+      assert_eq!(loaded_world.get([7, 19, 3]), CubeType::Air);
+      assert_eq!(loaded_world.get([7, 19, 4]), CubeType::Stone);
+
+      // the text format with a header still loads too
+      let path: std::path::PathBuf = std::env::temp_dir().join("termcraft_load_text_world.rmc");
+      let path: &str = path.to_str().unwrap();
+      std::fs::write(path, "-1 2 3 1 1 3\n403\n").unwrap();
+      let loaded_world: World = load_world(path);
+      std::fs::remove_file(path).unwrap();
+      assert_eq!(loaded_world.cubes().collect::<Vec<([i32; 3], CubeType)>>(), vec![([-1, 2, 3], CubeType::Dirt), ([-1, 2, 5], CubeType::Wood)]);
     }
     /// test if the math to check if the camera is pointing at triangles is correct
     #[test]
@@ -710,7 +3114,7 @@ mod tests {
       let b: Vector3 = Vector3 { x: 0.0, y: 0.0, z: 0.0 };
       let c: Vector3 = Vector3 { x: 0.0, y: 0.0, z: 0.0 };
       let normal: Vector3 = Vector3 { x: 1.0, y: 1.0, z: 1.0 };
-      let tris: Triangle3D = Triangle3D { a, b, c, color: CustomColor { r: 0, g: 0, b: 0 }, normal };
+      let tris: Triangle3D = Triangle3D { a, b, c, color: CustomColor { r: 0, g: 0, b: 0 }, normal, ambient_occlusion: [1.0; 3] };
 
       let camera_rotation_horizontal: f32 = -180.0 * (PI/180.0);
       let camera_rotation_vertical: f32 = -0.0 * (PI/180.0);